@@ -1,22 +1,50 @@
-use std::{collections::HashSet, fs, io, path::PathBuf};
+use std::{
+    collections::HashSet,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use clap::{Parser, Subcommand};
 use log::error;
 
 use crate::{
     cli::handlers::{
-        config::{current_config, edit_config, view_prompt_file},
+        cache::{cache_clear, cache_stats},
+        config::{
+            config_get, config_path, config_schema, config_set, config_unset, config_validate,
+            current_config, edit_config, view_prompt_file,
+        },
+        control::{flush_daemon, pause_daemon, reload_daemon, resume_daemon},
+        doctor::doctor,
         log::{error_logs, info_logs},
+        service::{install_service, uninstall_service},
         status::{daemon, restart_daemon, start_daemon, status_daemon, stop_daemon},
+        usage::usage_report,
     },
     config::config::Config,
-    manager::{ai::OpenAI, image::SSManager},
+    manager::{image::SSManager, usage::BudgetExceeded},
     utils::ss::get_screenshot_dir,
 };
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Args {
+    /// config file to use instead of the default (also PEEKSY_CONFIG)
+    #[arg(long = "config", global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// model to use, overriding PEEKSY_MODEL and the config file
+    #[arg(long, global = true)]
+    model: Option<String>,
+
+    /// prompt file to use, overriding PEEKSY_PROMPT_FILE and the config file
+    #[arg(long, global = true, value_name = "FILE")]
+    prompt_file: Option<String>,
+
+    /// OpenAI-compatible API base URL, overriding PEEKSY_BASE_URL and the config file
+    #[arg(long, global = true, value_name = "URL")]
+    base_url: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -27,17 +55,85 @@ pub enum Commands {
     Start,
     Stop,
     Restart,
-    Status,
+    Status {
+        /// print the daemon status as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    // service handlers
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+
+    // control socket handlers
+    Pause,
+    Resume,
+    Reload,
+    /// wait for queued screenshots to be processed
+    Flush,
 
     // config handlers
-    CurrentConfig,
+    CurrentConfig {
+        /// include the `.peeksy.toml` that applies to this file or directory
+        #[arg(long = "for", value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
     ViewPromptFile,
-    EditConfig,
+    EditConfig {
+        /// create or edit this profile instead of the top-level settings
+        #[arg(long)]
+        profile: Option<String>,
+    },
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
 
     // utils
+    /// inspect or empty the cache of answers
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+    /// show tokens and estimated cost by day, model and profile
+    Usage {
+        /// how many days back to report, today included
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        /// print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// check config, service, daemon and API health
+    Doctor {
+        /// skip checks that call the API
+        #[arg(long)]
+        offline: bool,
+        /// print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
     ProcessExistingScreenshots,
     Rename {
         file_path: String,
+        /// profile to name the file with
+        #[arg(long)]
+        profile: Option<String>,
+        /// pick the name from the candidates instead of taking the best one
+        #[arg(long)]
+        choose: bool,
+    },
+    /// print the name a file would be renamed to, without renaming it
+    Suggest {
+        file_path: String,
+        /// profile to name the file with
+        #[arg(long)]
+        profile: Option<String>,
+        /// print the name with the category, tags and description as JSON
+        #[arg(long)]
+        json: bool,
     },
     #[command(name = "daemon")]
     Daemon,
@@ -47,7 +143,63 @@ pub enum Commands {
     ErrorLogs,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// print the value of a config key
+    Get { key: String },
+    /// set a config key, values are parsed as JSON and fall back to a string
+    Set { key: String, value: String },
+    /// remove a config key
+    Unset { key: String },
+    /// print the path of the config file in use
+    Path,
+    /// check the config files and the effective config, exits non-zero on problems
+    Validate {
+        /// include the `.peeksy.toml` that applies to this file or directory
+        #[arg(long = "for", value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
+    /// print the JSON Schema of the config file for editor completion
+    Schema,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommands {
+    /// show how many answers are cached and how many requests they saved
+    Stats {
+        /// print the stats as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// remove every cached answer
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ServiceCommands {
+    /// write the launchd plist or systemd unit for this binary
+    Install,
+    /// remove the service definition, pid file and control socket
+    Uninstall,
+}
+
 impl Args {
+    pub fn config_path(&self) -> Option<PathBuf> {
+        self.config.clone()
+    }
+
+    // config keys set directly on the command line
+    pub fn config_overrides(&self) -> Vec<(String, String)> {
+        [
+            ("openai_model", &self.model),
+            ("openai_prompt_file_path", &self.prompt_file),
+            ("openai_base_url", &self.base_url),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| value.clone().map(|value| (key.to_string(), value)))
+        .collect()
+    }
+
     pub async fn execute(&self) {
         // execute command
         match &self.command {
@@ -56,31 +208,76 @@ impl Args {
             Commands::ErrorLogs => error_logs().await,
 
             // config handlers
-            Commands::CurrentConfig => current_config().await,
+            Commands::CurrentConfig { path } => current_config(path.as_deref()).await,
             Commands::ViewPromptFile => view_prompt_file().await,
-            Commands::EditConfig => edit_config().await,
+            Commands::EditConfig { profile } => edit_config(profile.as_deref()).await,
+            Commands::Config { command } => match command {
+                ConfigCommands::Get { key } => config_get(key).await,
+                ConfigCommands::Set { key, value } => config_set(key, value).await,
+                ConfigCommands::Unset { key } => config_unset(key).await,
+                ConfigCommands::Path => config_path().await,
+                ConfigCommands::Validate { path } => config_validate(path.as_deref()).await,
+                ConfigCommands::Schema => config_schema().await,
+            },
 
             // daemon handlers
             Commands::Start => start_daemon().await,
             Commands::Stop => stop_daemon().await,
             Commands::Restart => restart_daemon().await,
-            Commands::Status => status_daemon().await,
+            Commands::Status { json } => status_daemon(*json).await,
             Commands::Daemon => daemon().await,
 
+            // service handlers
+            Commands::Service { command } => match command {
+                ServiceCommands::Install => install_service().await,
+                ServiceCommands::Uninstall => uninstall_service().await,
+            },
+
+            // control socket handlers
+            Commands::Pause => pause_daemon().await,
+            Commands::Resume => resume_daemon().await,
+            Commands::Reload => reload_daemon().await,
+            Commands::Flush => flush_daemon().await,
+
             // utils handlers
-            Commands::Rename { file_path } => rename_file(file_path).await,
+            Commands::Doctor { offline, json } => doctor(*offline, *json).await,
+            Commands::Cache { command } => match command {
+                CacheCommands::Stats { json } => cache_stats(*json).await,
+                CacheCommands::Clear => cache_clear().await,
+            },
+            Commands::Usage { days, json } => usage_report(*days, *json).await,
+            Commands::Rename {
+                file_path,
+                profile,
+                choose,
+            } => rename_file(file_path, profile.as_deref(), *choose).await,
+            Commands::Suggest {
+                file_path,
+                profile,
+                json,
+            } => suggest_name(file_path, profile.as_deref(), *json).await,
             Commands::ProcessExistingScreenshots => process_existing_screenshots().await,
         }
     }
 }
 
-async fn rename_file(file_name: &str) {
-    let config = Config::fetch().expect("Failed to fetch config");
-    if !config.ready() {
-        error!("Config is not ready. Please run `peeksy edit-config` to set it.");
-        return;
+// the manager for file_name with the config of its directory and profile
+fn manager_for(file_name: &Path, profile: Option<&str>) -> Result<SSManager, anyhow::Error> {
+    let dir = file_name
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let config = Config::fetch_for(&dir, profile)?;
+    if let Err(e) = config.validate() {
+        return Err(anyhow::anyhow!(
+            "Config is not ready ({}). Please run `peeksy edit-config` to set it.",
+            e
+        ));
     }
+    SSManager::from_config(&config).map_err(|e| anyhow::anyhow!("Invalid config: {}", e))
+}
 
+async fn rename_file(file_name: &str, profile: Option<&str>, choose: bool) {
     let file_name = PathBuf::from(file_name);
 
     if !is_image(&file_name) {
@@ -91,32 +288,106 @@ async fn rename_file(file_name: &str) {
         return;
     }
 
-    let config = Config::fetch().expect("Failed to fetch config");
-    let ai = OpenAI::new(
-        config.openai_api_key.unwrap(),
-        config.openai_prompt_file_path.unwrap(),
-        config.openai_model.unwrap(),
-    );
+    let ss_manager = match manager_for(&file_name, profile) {
+        Ok(ss_manager) => ss_manager,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
 
-    let ss_manager = SSManager::new(ai);
-    let resp = ss_manager.process_random_image(&file_name).await;
+    let resp = if choose {
+        choose_name(&ss_manager, &file_name).await
+    } else {
+        ss_manager.process_random_image(&file_name).await
+    };
     if let Err(e) = resp {
         error!("Error processing file: {:?}", e);
     }
 }
 
+// lists the candidates, best first, and renames to the one picked
+async fn choose_name(ss_manager: &SSManager, file_name: &PathBuf) -> Result<(), anyhow::Error> {
+    let candidates = ss_manager.candidates(file_name).await?;
+    println!("Names for {:?}:", file_name);
+    for (i, candidate) in candidates.iter().enumerate() {
+        if candidate.is_valid() {
+            println!("  {}) {}", i + 1, candidate.file_name);
+        } else {
+            println!(
+                "  {}) {}  ⚠️ {}",
+                i + 1,
+                candidate.file_name,
+                candidate.problems.join(", ")
+            );
+        }
+    }
+
+    loop {
+        println!("Choose a name [1], or q to keep the current name: ");
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        let input = input.trim();
+        if input.eq_ignore_ascii_case("q") {
+            return Ok(());
+        }
+        let choice = if input.is_empty() {
+            Some(0)
+        } else {
+            input.parse::<usize>().ok().and_then(|n| n.checked_sub(1))
+        };
+        match choice.and_then(|i| candidates.get(i)) {
+            Some(candidate) => {
+                ss_manager.rename_to(file_name, &candidate.file_name)?;
+                println!("✅ Renamed to {}", candidate.file_name);
+                return Ok(());
+            }
+            None => eprintln!("❌ Enter a number from 1 to {}", candidates.len()),
+        }
+    }
+}
+
+async fn suggest_name(file_name: &str, profile: Option<&str>, json: bool) {
+    let file_name = PathBuf::from(file_name);
+    if !is_image(&file_name) {
+        eprintln!("❌ File is not an image: {:?}", file_name);
+        std::process::exit(1);
+    }
+
+    let result = match manager_for(&file_name, profile) {
+        Ok(ss_manager) => ss_manager.suggest(&file_name).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok((name, suggestion)) if json => {
+            let mut output = serde_json::to_value(&suggestion).unwrap_or_default();
+            output["filename"] = name.into();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&output).unwrap_or_default()
+            );
+        }
+        Ok((name, _)) => println!("{}", name),
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 async fn process_existing_screenshots() {
     let ss_dir = get_screenshot_dir();
 
-    let files = fs::read_dir(ss_dir).unwrap();
+    let files = fs::read_dir(&ss_dir).unwrap();
 
-    let config = Config::fetch().unwrap();
-    let ai = OpenAI::new(
-        config.openai_api_key.unwrap(),
-        config.openai_prompt_file_path.unwrap(),
-        config.openai_model.unwrap(),
-    );
-    let ss_manager = SSManager::new(ai);
+    let ss_manager =
+        match Config::fetch_for(&ss_dir, None).and_then(|config| SSManager::from_config(&config)) {
+            Ok(ss_manager) => ss_manager,
+            Err(e) => {
+                error!("Invalid config: {}", e);
+                return;
+            }
+        };
 
     let mut screenshot = vec![];
     for file in files {
@@ -141,12 +412,16 @@ async fn process_existing_screenshots() {
     for file in screenshot {
         let resp = ss_manager.process_random_image(&file).await;
         if let Err(e) = resp {
+            if e.downcast_ref::<BudgetExceeded>().is_some() {
+                eprintln!("❌ {}, stopping", e);
+                return;
+            }
             error!("Error processing file: {:?}", e);
         }
     }
 }
 
-fn is_image(file_name: &PathBuf) -> bool {
+fn is_image(file_name: &Path) -> bool {
     // Set of allowed image extensions (lowercase)
     let image_exts: HashSet<&'static str> = [
         "png", "jpg", "jpeg", "gif", "webp", "heic", "heif", "bmp", "tiff", "tif",
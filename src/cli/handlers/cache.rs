@@ -0,0 +1,33 @@
+use crate::{config::config::Config, manager::cache};
+
+pub async fn cache_stats(json: bool) {
+    let ttl = Config::fetch()
+        .map(|c| c.cache_ttl())
+        .unwrap_or_else(|_| Config::default().cache_ttl());
+    let stats = cache::stats(ttl);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+        return;
+    }
+    if ttl.is_zero() {
+        println!("⚠️ The cache is off, set `cache_ttl_days` to turn it on");
+    }
+    println!("Entries: {} ({} expired)", stats.entries, stats.expired);
+    println!("Requests saved: {}", stats.hits);
+    println!("Size: {:.1} KB", stats.size_bytes as f64 / 1024.0);
+    if let (Some(oldest), Some(newest)) = (stats.oldest, stats.newest) {
+        println!(
+            "Oldest: {}, newest: {}",
+            oldest.format("%Y-%m-%d %H:%M"),
+            newest.format("%Y-%m-%d %H:%M")
+        );
+    }
+}
+
+pub async fn cache_clear() {
+    match cache::clear() {
+        Ok(count) => println!("✅ Removed {} cached answers", count),
+        Err(e) => eprintln!("❌ Failed to clear the cache: {}", e),
+    }
+}
@@ -1,18 +1,38 @@
-use crate::config;
+use std::path::{Path, PathBuf};
 
-pub async fn edit_config() {
-    let mut config = config::config::Config::fetch().expect("Failed to fetch config");
-    config.edit_config().expect("Failed to edit config");
+use crate::config::{self, files, layers::Source, migrate, setup, validate};
+
+pub async fn edit_config(profile: Option<&str>) {
+    let mut config = config::config::Config::fetch_file().unwrap_or_else(|e| fail(e));
+    match profile {
+        Some(profile) => config
+            .edit_profile(profile)
+            .expect("Failed to edit profile"),
+        None => config.edit_config().expect("Failed to edit config"),
+    }
     println!("Config edited successfully");
 }
 
-pub async fn current_config() {
-    let config = config::config::Config::fetch().expect("Failed to fetch config");
-    println!("{}", serde_json::to_string_pretty(&config).unwrap());
+// prints every effective value next to where it came from. with a path,
+// the `.peeksy.toml` that applies to it is included.
+pub async fn current_config(path: Option<&Path>) {
+    let dir = path.map(project_dir);
+    let resolved =
+        config::config::Config::resolve_for(dir.as_deref(), None).unwrap_or_else(|e| fail(e));
+    let values = serde_json::to_value(&resolved.config).unwrap();
+
+    let mut report = serde_json::Map::new();
+    if let serde_json::Value::Object(values) = values {
+        for (key, value) in values {
+            let source = resolved.sources.get(&key);
+            report.insert(key, serde_json::json!({ "value": value, "source": source }));
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
 }
 
 pub async fn view_prompt_file() {
-    let config = config::config::Config::fetch().expect("Failed to fetch config");
+    let config = config::config::Config::fetch().unwrap_or_else(|e| fail(e));
     let prompt_file = config.get_openai_prompt_file_path();
     match prompt_file {
         Some(prompt_file) => {
@@ -24,3 +44,143 @@ pub async fn view_prompt_file() {
         }
     }
 }
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("❌ {}", message);
+    std::process::exit(1);
+}
+
+pub async fn config_get(key: &str) {
+    let config = config::config::Config::fetch().unwrap_or_else(|e| fail(e));
+    match config.get_key(key) {
+        Ok(serde_json::Value::String(value)) => println!("{}", value),
+        Ok(serde_json::Value::Null) => {}
+        Ok(value) => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+        Err(e) => fail(e),
+    }
+}
+
+pub async fn config_set(key: &str, value: &str) {
+    let mut config = config::config::Config::fetch_file().unwrap_or_else(|e| fail(e));
+    if let Err(e) = config.set_key(key, value).and_then(|_| config.save()) {
+        fail(e);
+    }
+}
+
+pub async fn config_unset(key: &str) {
+    let mut config = config::config::Config::fetch_file().unwrap_or_else(|e| fail(e));
+    let previous = config.get_openai_api_key();
+    if let Err(e) = config.unset_key(key).and_then(|_| config.save()) {
+        fail(e);
+    }
+
+    // drop the stored key along with the reference to it
+    if config.get_openai_api_key().is_none() {
+        if let Some(name) = previous.as_ref().and_then(|key| {
+            key.expose()
+                .strip_prefix(config::secrets::REFERENCE_PREFIX)
+                .map(str::to_string)
+        }) {
+            config::secrets::delete(&name);
+        }
+    }
+}
+
+pub async fn config_path() {
+    println!("{}", setup::get_config_path().display());
+}
+
+// the directory whose `.peeksy.toml` applies to path
+fn project_dir(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.to_path_buf()
+    } else {
+        path.parent().map(Path::to_path_buf).unwrap_or_default()
+    }
+}
+
+// checks every config file and the effective config, exiting non-zero on
+// any problem so it can gate scripts and CI
+pub async fn config_validate(path: Option<&Path>) {
+    let dir = path.map(project_dir);
+
+    let mut layer_files = vec![];
+    let system = files::system_config_path();
+    if system.is_file() {
+        layer_files.push((Source::System, system));
+    }
+    layer_files.push((Source::User, setup::get_config_path()));
+    if let Some(project) = dir.as_deref().and_then(files::find_project_config) {
+        layer_files.push((Source::Project, project));
+    }
+
+    let mut problems = vec![];
+    for (source, path) in &layer_files {
+        let mut value = match files::read_value(path) {
+            Ok(value) => value,
+            Err(e) => {
+                problems.push(e.to_string());
+                continue;
+            }
+        };
+
+        let mut file_problems = vec![];
+        if *source == Source::Project {
+            file_problems.extend(files::check_project(&value));
+        }
+        if let Err(problem) = migrate::migrate(&mut value) {
+            file_problems.push(problem);
+        }
+        file_problems.extend(validate::check_file(&value));
+        problems.extend(
+            file_problems
+                .iter()
+                .map(|problem| format!("{} (in {})", problem, path.display())),
+        );
+    }
+
+    // values from the environment or flags can only be checked once the
+    // files themselves parse
+    if problems.is_empty() {
+        let resolved =
+            config::config::Config::resolve_for(dir.as_deref(), None).unwrap_or_else(|e| fail(e));
+        for warning in validate::prompt_warnings(&resolved.config) {
+            println!("⚠️ {}", warning);
+        }
+        for problem in validate::check_config(&resolved.config) {
+            let origin = match resolved.sources.get(&problem.key) {
+                Some(Source::Env) => " (from environment)".to_string(),
+                Some(Source::Flag) => " (from command line)".to_string(),
+                Some(Source::Profile) => format!(
+                    " (from profile {})",
+                    resolved.config.profile.as_deref().unwrap_or_default()
+                ),
+                Some(source) => resolved
+                    .files
+                    .iter()
+                    .find(|(file_source, _)| file_source == source)
+                    .map(|(_, path)| format!(" (in {})", path.display()))
+                    .unwrap_or_default(),
+                None => String::new(),
+            };
+            problems.push(format!("{}{}", problem, origin));
+        }
+    }
+
+    if problems.is_empty() {
+        for (_, path) in &layer_files {
+            println!("✅ {} is valid", path.display());
+        }
+        return;
+    }
+    for problem in &problems {
+        eprintln!("❌ {}", problem);
+    }
+    std::process::exit(1);
+}
+
+// JSON Schema of the config file, for editor completion
+pub async fn config_schema() {
+    let schema = schemars::schema_for!(config::config::Config);
+    println!("{}", serde_json::to_string_pretty(&schema).unwrap());
+}
@@ -0,0 +1,26 @@
+use crate::daemon::control::{self, Request};
+
+async fn send_request(request: Request, success: &str) {
+    match control::send(request).await {
+        Ok(resp) if resp.ok => println!("✅ {}", success),
+        Ok(resp) => println!("❌ {}", resp.error.unwrap_or_default()),
+        Err(e) => println!("❌ {}", e),
+    }
+}
+
+pub async fn pause_daemon() {
+    send_request(Request::Pause, "Peeksy daemon paused").await;
+}
+
+pub async fn resume_daemon() {
+    send_request(Request::Resume, "Peeksy daemon resumed").await;
+}
+
+pub async fn reload_daemon() {
+    send_request(Request::Reload, "Peeksy daemon config reloaded").await;
+}
+
+pub async fn flush_daemon() {
+    println!("Waiting for queued screenshots to be processed...");
+    send_request(Request::Flush, "Peeksy daemon queue is empty").await;
+}
@@ -0,0 +1,244 @@
+use std::{fs, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    cli::handlers::status::is_daemon_running,
+    config::{config::Config, setup},
+    manager::{ai::OpenAI, image::SSManager},
+    service::service::service_manager,
+    utils::ss::get_screenshot_dir,
+};
+
+// a real image for the sample rename, so the model has something to name
+const SAMPLE_IMAGE: &[u8] = include_bytes!("../../../assets/peeksy.png");
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Pass,
+    Fail,
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+    detail: String,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Pass,
+            detail: detail.into(),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Fail,
+            detail: detail.into(),
+        }
+    }
+
+    fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            outcome: Outcome::Skip,
+            detail: detail.into(),
+        }
+    }
+
+    fn from_result(name: &'static str, result: Result<String, anyhow::Error>) -> Self {
+        match result {
+            Ok(detail) => Self::pass(name, detail),
+            Err(e) => Self::fail(name, e.to_string()),
+        }
+    }
+}
+
+fn check_config() -> (Check, Option<Config>) {
+    let config = match Config::fetch() {
+        Ok(config) => config,
+        Err(e) => return (Check::fail("config", e.to_string()), None),
+    };
+
+    let check = match config.validate() {
+        Ok(_) => Check::pass("config", setup::get_config_path().display().to_string()),
+        Err(e) => Check::fail("config", e.to_string()),
+    };
+    (check, Some(config))
+}
+
+fn check_prompt(config: &Config) -> Check {
+    Check::from_result(
+        "prompt_file",
+        config.read_prompt().and_then(|prompt| {
+            if prompt.trim().is_empty() {
+                return Err(anyhow::anyhow!("prompt file is empty"));
+            }
+            Ok(config.get_openai_prompt_file_path().unwrap_or_default())
+        }),
+    )
+}
+
+fn check_watch_dir() -> Check {
+    let dir = get_screenshot_dir();
+    if !dir.is_dir() {
+        return Check::fail("watch_dir", format!("{} does not exist", dir.display()));
+    }
+
+    // renaming needs to create and delete files in the directory
+    let probe = dir.join(".peeksy-doctor");
+    let result = fs::write(&probe, b"peeksy").and_then(|_| fs::remove_file(&probe));
+    match result {
+        Ok(_) => Check::pass("watch_dir", format!("{} is writable", dir.display())),
+        Err(e) => Check::fail(
+            "watch_dir",
+            format!("{} is not writable: {}", dir.display(), e),
+        ),
+    }
+}
+
+fn check_service() -> Check {
+    let service = service_manager();
+    if !service.is_installed() {
+        return Check::fail(
+            "service",
+            format!(
+                "{} service is not installed, use `peeksy service install`",
+                service.name()
+            ),
+        );
+    }
+
+    let state = if service.is_running() {
+        "running"
+    } else if service.is_loaded() {
+        "loaded but not running"
+    } else {
+        "installed but not loaded"
+    };
+    Check::pass(
+        "service",
+        format!(
+            "{} service at {} is {}",
+            service.name(),
+            service.definition_path().display(),
+            state
+        ),
+    )
+}
+
+async fn check_daemon() -> Check {
+    match is_daemon_running().await {
+        (true, Some(pid)) => Check::pass("daemon", format!("running with PID {}", pid)),
+        (true, None) => Check::pass("daemon", "running"),
+        (false, _) => Check::fail("daemon", "not running, use `peeksy start`"),
+    }
+}
+
+async fn check_api(ai: &OpenAI) -> Check {
+    match ai.check_auth().await {
+        Ok(_) => Check::pass("api", "API key accepted"),
+        Err(e) => Check::fail("api", e.to_string()),
+    }
+}
+
+// renames a copy of the sample image inside a scratch directory
+async fn check_sample_rename(ai: OpenAI) -> Check {
+    let dir = std::env::temp_dir().join(format!("peeksy-doctor-{}", std::process::id()));
+    let result = sample_rename(ai, &dir).await;
+    fs::remove_dir_all(&dir).ok();
+    Check::from_result("sample_rename", result)
+}
+
+async fn sample_rename(ai: OpenAI, dir: &Path) -> Result<String, anyhow::Error> {
+    fs::create_dir_all(dir)?;
+    let sample = dir.join("sample.png");
+    fs::write(&sample, SAMPLE_IMAGE)?;
+
+    SSManager::new(ai.without_cache())
+        .process_random_image(&sample)
+        .await?;
+
+    let renamed = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|path| path != &sample)
+        .ok_or_else(|| anyhow::anyhow!("sample image was not renamed"))?;
+    Ok(format!(
+        "sample.png -> {}",
+        renamed.file_name().unwrap_or_default().to_string_lossy()
+    ))
+}
+
+pub async fn doctor(offline: bool, json: bool) {
+    let mut checks = vec![];
+
+    let (config_check, config) = check_config();
+    let config_ok = config_check.outcome == Outcome::Pass;
+    checks.push(config_check);
+
+    let ai = match config.as_ref() {
+        Some(config) => {
+            checks.push(check_prompt(config));
+            if config_ok {
+                OpenAI::from_config(config).ok()
+            } else {
+                None
+            }
+        }
+        None => {
+            checks.push(Check::skip("prompt_file", "config could not be read"));
+            None
+        }
+    };
+
+    checks.push(check_watch_dir());
+    checks.push(check_service());
+    checks.push(check_daemon().await);
+
+    match (offline, ai) {
+        (true, _) => {
+            checks.push(Check::skip("api", "offline"));
+            checks.push(Check::skip("sample_rename", "offline"));
+        }
+        (false, None) => {
+            checks.push(Check::skip("api", "config is not valid"));
+            checks.push(Check::skip("sample_rename", "config is not valid"));
+        }
+        (false, Some(ai)) => {
+            let api_check = check_api(&ai).await;
+            let api_ok = api_check.outcome == Outcome::Pass;
+            checks.push(api_check);
+            if api_ok {
+                checks.push(check_sample_rename(ai).await);
+            } else {
+                checks.push(Check::skip("sample_rename", "API check failed"));
+            }
+        }
+    }
+
+    let failed = checks.iter().any(|c| c.outcome == Outcome::Fail);
+    if json {
+        let report = serde_json::json!({ "ok": !failed, "checks": checks });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        for check in &checks {
+            let icon = match check.outcome {
+                Outcome::Pass => "✅",
+                Outcome::Fail => "❌",
+                Outcome::Skip => "⏭️ ",
+            };
+            println!("{} {}: {}", icon, check.name, check.detail);
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -1,3 +1,8 @@
+pub mod cache;
 pub mod config;
+pub mod control;
+pub mod doctor;
 pub mod log;
+pub mod service;
 pub mod status;
+pub mod usage;
@@ -0,0 +1,51 @@
+use crate::{
+    cli::handlers::status::{is_daemon_running, terminate_daemon},
+    config::config::Config,
+    daemon::{control, pid},
+    service::service::{service_manager, ServiceDefinition},
+};
+
+pub async fn install_service() {
+    let definition =
+        match Config::fetch().and_then(|config| ServiceDefinition::from_config(&config)) {
+            Ok(definition) => definition,
+            Err(e) => {
+                println!("❌ {}", e);
+                return;
+            }
+        };
+
+    let service = service_manager();
+    if let Err(e) = service.install(&definition) {
+        println!("❌ Failed to install {} service: {}", service.name(), e);
+        return;
+    }
+
+    println!(
+        "✅ Peeksy {} service installed at {}",
+        service.name(),
+        service.definition_path().display()
+    );
+    if service.is_loaded() {
+        println!("Use `peeksy restart` to apply the new service definition");
+    } else {
+        println!("Use `peeksy start` to start the daemon");
+    }
+}
+
+pub async fn uninstall_service() {
+    let service = service_manager();
+    service.unload();
+    if let (true, Some(pid)) = is_daemon_running().await {
+        terminate_daemon(pid).await;
+    }
+
+    if let Err(e) = service.uninstall() {
+        println!("❌ Failed to uninstall {} service: {}", service.name(), e);
+        return;
+    }
+    pid::remove_pid();
+    control::remove_socket();
+
+    println!("✅ Peeksy {} service uninstalled", service.name());
+}
@@ -1,54 +1,143 @@
+use std::time::{Duration, Instant};
+
+use log::error;
+
 use crate::{
-    daemon::{daemon, pid},
-    launchd::launchd,
+    config,
+    daemon::{
+        control::{self, Request},
+        crash, daemon, pid,
+    },
+    service::service::service_manager,
 };
 
-const NOTE: &str = "⚠️ Note: If you have updated the screenshot directory, you need to restart the daemon.\n Use `peeksy restart` to restart the daemon.";
+const NOTE: &str = "ℹ️ Note: Changes to the config, prompt file and screenshot directory are picked up automatically.\n If a change does not apply, check `peeksy error-logs` for a rejected config.";
+
+const NOT_INSTALLED: &str =
+    "Peeksy service is not installed. Use `peeksy service install` to install it.";
 
 pub async fn is_daemon_running() -> (bool, Option<u32>) {
-    let pid = pid::get_pid();
-    match pid {
-        Ok(pid) => {
-            // check if process is running at saved pid
-            let process = std::process::Command::new("ps")
-                .args(["-p", &pid.to_string()])
-                .output()
-                .unwrap();
-            (process.status.success(), Some(pid))
+    // the pid file lock is not probed here, as holding it even briefly
+    // would stop a daemon starting at the same moment. a pid left behind by
+    // a crash, or reused by another process, fails the process check.
+    match pid::get_pid() {
+        Ok(pid) if pid::is_peeksy_daemon(pid) => (true, Some(pid)),
+        _ => (false, None),
+    }
+}
+
+// stops a daemon that is not managed by the service manager, e.g. one started
+// with `peeksy daemon`, giving it time to drain its queue
+pub async fn terminate_daemon(pid: u32) {
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGTERM);
+    }
+
+    let timeout = config::config::Config::fetch()
+        .map(|config| config.shutdown_timeout())
+        .unwrap_or(Duration::from_secs(10));
+    let deadline = Instant::now() + timeout + Duration::from_secs(2);
+    while Instant::now() < deadline {
+        if !is_daemon_running().await.0 {
+            return;
         }
-        Err(_) => (false, None),
+        tokio::time::sleep(Duration::from_millis(200)).await;
     }
+    println!("⚠️ Peeksy daemon with PID {} did not stop in time", pid);
 }
 
-pub async fn status_daemon() {
-    let launchd = launchd::LaunchD::new();
-    if launchd.is_loaded().await && launchd.is_running().await {
+pub async fn status_daemon(json: bool) {
+    let status = control::send(Request::Status)
+        .await
+        .ok()
+        .and_then(|resp| resp.status);
+    let crashes = crash::get_crash_report();
+
+    if json {
+        let report = match status {
+            Some(status) => {
+                serde_json::json!({ "running": true, "status": status, "crashes": crashes })
+            }
+            None => serde_json::json!({ "running": false, "crashes": crashes }),
+        };
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+
+    let service = service_manager();
+    if service.is_loaded() && service.is_running() {
         println!("Peeksy daemon is already running\n{}", NOTE);
     } else {
         println!("Peeksy daemon is not running");
     }
+
+    if let Some(status) = status {
+        println!("PID: {}", status.pid);
+        println!("Uptime: {}s", status.uptime_secs);
+        match &status.paused_reason {
+            Some(reason) => println!("Paused: {} ({})", status.paused, reason),
+            None => println!("Paused: {}", status.paused),
+        }
+        println!("Queue depth: {}", status.queue_depth);
+        for (dir, profile) in &status.watched {
+            match profile {
+                Some(profile) => println!("Watching: {} (profile {})", dir.display(), profile),
+                None => println!("Watching: {}", dir.display()),
+            }
+        }
+        println!(
+            "Processed: {}, failed: {}, skipped: {}",
+            status.processed, status.failed, status.skipped
+        );
+        if let Some(last_error) = status.last_error {
+            println!("Last error: {}", last_error);
+        }
+    }
+
+    if crashes.count > 0 {
+        println!("Crashes: {}", crashes.count);
+        if let Some(message) = crashes.last_message {
+            println!("Last crash: {}", message);
+        }
+    }
 }
 
 pub async fn restart_daemon() {
-    let launchd = launchd::LaunchD::new();
-    launchd.unload().await;
-    launchd.load().await;
+    let service = service_manager();
+    if !service.is_installed() {
+        println!("{}", NOT_INSTALLED);
+        return;
+    }
+    service.unload();
+    service.load();
     println!("✅ Peeksy daemon restarted successfully");
 }
 
 pub async fn stop_daemon() {
-    let launchd = launchd::LaunchD::new();
-    launchd.unload().await;
+    let service = service_manager();
+    service.unload();
+    if let (true, Some(pid)) = is_daemon_running().await {
+        terminate_daemon(pid).await;
+    }
     println!("✅ Peeksy daemon stopped successfully");
 }
 
 pub async fn start_daemon() {
-    let launchd = launchd::LaunchD::new();
-    if launchd.is_loaded().await && launchd.is_running().await {
+    if let (true, Some(pid)) = is_daemon_running().await {
+        println!("Peeksy daemon is already running with PID {}", pid);
+        return;
+    }
+
+    let service = service_manager();
+    if !service.is_installed() {
+        println!("{}", NOT_INSTALLED);
+        return;
+    }
+    if service.is_loaded() && service.is_running() {
         println!("Peeksy daemon is already running");
         return;
     }
-    launchd.load().await;
+    service.load();
     println!("✅ Peeksy daemon started successfully");
 }
 
@@ -58,5 +147,9 @@ pub async fn daemon() {
         println!("Peeksy daemon is already running with PID {}", pid.unwrap());
         return;
     }
-    daemon::run().await;
+    if let Err(e) = daemon::run().await {
+        error!("{}", e);
+        // a non-zero exit lets the service manager's restart policy kick in
+        std::process::exit(1);
+    }
 }
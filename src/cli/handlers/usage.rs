@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+use serde::Serialize;
+
+use crate::{
+    config::{budget::Budget, config::Config},
+    manager::usage::{self, Record},
+};
+
+// requests grouped by day, model and profile
+#[derive(Debug, Default, Serialize)]
+struct Row {
+    day: String,
+    model: String,
+    profile: String,
+    requests: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cost_usd: f64,
+    // requests to models without a known price, left out of the cost
+    unpriced: u64,
+}
+
+fn rows(records: &[Record]) -> Vec<Row> {
+    let mut rows: BTreeMap<(String, String, String), Row> = BTreeMap::new();
+    for record in records {
+        let day = record.time.format("%Y-%m-%d").to_string();
+        let profile = record
+            .profile
+            .clone()
+            .unwrap_or_else(|| "default".to_string());
+        let row = rows
+            .entry((day.clone(), record.model.clone(), profile.clone()))
+            .or_insert_with(|| Row {
+                day,
+                model: record.model.clone(),
+                profile,
+                ..Default::default()
+            });
+        row.requests += 1;
+        row.prompt_tokens += record.prompt_tokens;
+        row.completion_tokens += record.completion_tokens;
+        match record.cost_usd {
+            Some(cost) => row.cost_usd += cost,
+            None => row.unpriced += 1,
+        }
+    }
+    rows.into_values().collect()
+}
+
+fn cap(spent: f64, soft: Option<f64>, hard: Option<f64>) -> String {
+    let mut caps = vec![];
+    if let Some(soft) = soft {
+        caps.push(format!("soft ${:.2}", soft));
+    }
+    if let Some(hard) = hard {
+        caps.push(format!("hard ${:.2}", hard));
+    }
+    if caps.is_empty() {
+        format!("${:.4}", spent)
+    } else {
+        format!("${:.4} of {}", spent, caps.join(", "))
+    }
+}
+
+pub async fn usage_report(days: u32, json: bool) {
+    let since = usage::start_of_day() - Duration::days(i64::from(days.max(1)) - 1);
+    let rows = rows(&usage::records_since(since));
+    let today = usage::spent_since(usage::start_of_day());
+    let month = usage::spent_since(usage::start_of_month());
+    let budget = Config::fetch().map(|c| c.budget()).unwrap_or_default();
+
+    if json {
+        let report = serde_json::json!({
+            "since": since.format("%Y-%m-%d").to_string(),
+            "rows": rows,
+            "today_usd": today,
+            "month_usd": month,
+            "budget": budget,
+        });
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+
+    println!("Usage since {}", since.format("%Y-%m-%d"));
+    if rows.is_empty() {
+        println!("No requests recorded");
+    } else {
+        println!(
+            "{:<10}  {:<16}  {:<10}  {:>8}  {:>10}  {:>10}  {:>10}",
+            "Day", "Model", "Profile", "Requests", "Prompt", "Completion", "Cost"
+        );
+        for row in &rows {
+            let cost = if row.unpriced > 0 {
+                format!("${:.4}*", row.cost_usd)
+            } else {
+                format!("${:.4}", row.cost_usd)
+            };
+            println!(
+                "{:<10}  {:<16}  {:<10}  {:>8}  {:>10}  {:>10}  {:>10}",
+                row.day,
+                row.model,
+                row.profile,
+                row.requests,
+                row.prompt_tokens,
+                row.completion_tokens,
+                cost
+            );
+        }
+        if rows.iter().any(|row| row.unpriced > 0) {
+            println!("* some requests used a model without a price, add it to `prices`");
+        }
+    }
+
+    let Budget {
+        daily_soft_usd,
+        daily_hard_usd,
+        monthly_soft_usd,
+        monthly_hard_usd,
+        ..
+    } = budget;
+    println!("Today: {}", cap(today, daily_soft_usd, daily_hard_usd));
+    println!(
+        "This month: {}",
+        cap(month, monthly_soft_usd, monthly_hard_usd)
+    );
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// spending caps in US dollars. past a soft cap requests go to the fallback
+// profile when there is one, past a hard cap they are never sent to the
+// paid provider and the daemon pauses until the cap resets.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Budget {
+    #[schemars(description = "Daily spend after which the fallback profile is used")]
+    pub daily_soft_usd: Option<f64>,
+    #[schemars(description = "Daily spend after which renaming pauses until the next day")]
+    pub daily_hard_usd: Option<f64>,
+    #[schemars(description = "Monthly spend after which the fallback profile is used")]
+    pub monthly_soft_usd: Option<f64>,
+    #[schemars(description = "Monthly spend after which renaming pauses until the next month")]
+    pub monthly_hard_usd: Option<f64>,
+    #[schemars(description = "Profile used past a cap, e.g. one for a local model")]
+    pub fallback_profile: Option<String>,
+}
+
+impl Budget {
+    pub fn has_caps(&self) -> bool {
+        [
+            self.daily_soft_usd,
+            self.daily_hard_usd,
+            self.monthly_soft_usd,
+            self.monthly_hard_usd,
+        ]
+        .iter()
+        .any(Option::is_some)
+    }
+}
+
+// what a model costs per million tokens
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Price {
+    #[schemars(description = "US dollars per million prompt tokens")]
+    pub input_usd_per_million: f64,
+    #[schemars(description = "US dollars per million completion tokens")]
+    pub output_usd_per_million: f64,
+}
+
+impl Price {
+    pub fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input_usd_per_million
+            + completion_tokens as f64 * self.output_usd_per_million)
+            / 1_000_000.0
+    }
+}
+
+// list prices of the OpenAI vision models, overridden by `prices`
+const DEFAULT_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4.1", 2.00, 8.00),
+    ("gpt-4.1-mini", 0.40, 1.60),
+    ("gpt-4.1-nano", 0.10, 0.40),
+];
+
+pub fn default_prices() -> BTreeMap<String, Price> {
+    DEFAULT_PRICES
+        .iter()
+        .map(|(model, input, output)| {
+            (
+                model.to_string(),
+                Price {
+                    input_usd_per_million: *input,
+                    output_usd_per_million: *output,
+                },
+            )
+        })
+        .collect()
+}
+
+// the price of model, matching dated versions such as `gpt-4o-2024-08-06`
+// by the longest known prefix
+pub fn price_for(prices: &BTreeMap<String, Price>, model: &str) -> Option<Price> {
+    prices
+        .iter()
+        .filter(|(name, _)| model.starts_with(name.as_str()))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, price)| *price)
+}
@@ -1,54 +1,366 @@
 use log::{error, info};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use serde_json;
-use std::{fs::File, io};
+use serde_json::{self, Value};
+use std::{collections::BTreeMap, io, path::Path, time::Duration};
 
-use crate::config::setup;
+use crate::{
+    config::{
+        budget::{self, Budget, Price},
+        files,
+        layers::{self, Resolved, Source},
+        migrate::CURRENT_VERSION,
+        profile::{self, Profile},
+        secrets::{self, Secret},
+        setup,
+        validate::{self, Problem},
+    },
+    manager::{
+        cache, examples,
+        ratelimit::{self, RateLimit},
+        score,
+        style::NamingStyle,
+    },
+    service::service::RestartPolicy,
+};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+pub const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+// name of the api key in the secret store
+const API_KEY_SECRET: &str = "openai_api_key";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
 pub struct Config {
-    pub openai_api_key: Option<String>,
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none")]
+    #[schemars(description = "JSON Schema for editor completion, see `peeksy config schema`")]
+    pub schema: Option<String>,
+    #[schemars(description = "Config file format version, upgraded automatically")]
+    pub version: Option<u32>,
+    #[schemars(description = "Reference to the API key in the secret store")]
+    pub openai_api_key: Option<Secret>,
+    #[schemars(description = "File holding the prompt sent with every image")]
     pub openai_prompt_file_path: Option<String>,
+    #[schemars(description = "Model used for naming, e.g. gpt-4o")]
     pub openai_model: Option<String>,
+    #[schemars(description = "Base URL of an OpenAI-compatible API")]
+    pub openai_base_url: Option<String>,
+    #[schemars(description = "System message sent before the prompt, a built-in one by default")]
+    pub openai_system_prompt: Option<String>,
+    #[schemars(
+        description = "Directory of example images named the way files should be named, sent as few-shot examples"
+    )]
+    pub examples_dir: Option<String>,
+    #[schemars(description = "Most examples sent with every image, 5 by default")]
+    pub max_examples: Option<usize>,
+    #[schemars(
+        description = "Ask for a JSON answer with category, tags and description, on by default. Servers without structured output fall back to plain text."
+    )]
+    pub structured_output: Option<bool>,
+    #[schemars(
+        description = "Prices per model, on top of the built-in OpenAI ones, used to estimate costs"
+    )]
+    pub prices: Option<BTreeMap<String, Price>>,
+    #[schemars(description = "Daily and monthly spending caps")]
+    pub budget: Option<Budget>,
+    #[schemars(
+        description = "Days a name is reused for the same image and request, 30 by default, 0 turns the cache off"
+    )]
+    pub cache_ttl_days: Option<u64>,
+    #[schemars(description = "Most answers kept in the cache, 10000 by default")]
+    pub cache_max_entries: Option<usize>,
+    #[schemars(
+        description = "Requests and tokens per minute by API base URL, shared by every Peeksy process"
+    )]
+    pub rate_limits: Option<BTreeMap<String, RateLimit>>,
+    #[schemars(description = "Rename screenshots taken while the daemon was stopped")]
+    pub catch_up_on_start: Option<bool>,
+    #[schemars(description = "Seconds a stopping daemon may spend on queued renames")]
+    pub shutdown_timeout_secs: Option<u64>,
+    #[schemars(description = "Absolute path for the service's stdout")]
+    pub service_stdout_path: Option<String>,
+    #[schemars(description = "Absolute path for the service's stderr")]
+    pub service_stderr_path: Option<String>,
+    #[schemars(description = "Extra environment variables for the service")]
+    pub service_environment: Option<BTreeMap<String, String>>,
+    #[schemars(description = "When the service manager restarts the daemon")]
+    pub service_restart: Option<RestartPolicy>,
+    #[schemars(description = "Consecutive crashes tolerated before the daemon exits")]
+    pub supervisor_max_restarts: Option<u32>,
+    #[schemars(description = "Seconds before the first restart after a crash")]
+    pub supervisor_backoff_secs: Option<u64>,
+    #[schemars(
+        description = "Template for the new file name, e.g. `{date}_{ai}` or `{ai}-{seq:03}`"
+    )]
+    pub naming_template: Option<String>,
+    #[schemars(
+        description = "Case and separators for the words the model suggests, as returned when unset"
+    )]
+    pub naming_style: Option<NamingStyle>,
+    #[schemars(
+        description = "Names asked for per image, the best one by the naming rules is used"
+    )]
+    pub candidates: Option<u32>,
+    #[schemars(
+        description = "Generic words a good name avoids, `image`, `picture`, `photo` and `screenshot` by default"
+    )]
+    pub banned_words: Option<Vec<String>>,
+    #[schemars(
+        description = "Language of the suggested names, e.g. `ja` or `de-DE`, the model's choice when unset"
+    )]
+    pub language: Option<String>,
+    #[schemars(description = "Transliterate suggested names to ASCII, e.g. `café` to `cafe`")]
+    pub transliterate: Option<bool>,
+    #[schemars(
+        description = "Directory renamed files are moved to, next to the original by default"
+    )]
+    pub destination: Option<String>,
+    #[schemars(description = "Profile used when a command or directory does not pick one")]
+    pub profile: Option<String>,
+    #[schemars(description = "Named profiles, selected with --profile or per watched directory")]
+    pub profiles: Option<BTreeMap<String, Profile>>,
+    #[schemars(
+        description = "Directories the daemon watches next to the screenshot directory, with the profile used for each"
+    )]
+    pub watch_profiles: Option<BTreeMap<String, String>>,
 }
 
 impl Config {
+    // the effective config: flags, then environment, then the project file,
+    // then the selected profile, then the user and system files, then
+    // defaults. use `fetch_file` when the result is saved back.
     pub fn fetch() -> Result<Self, anyhow::Error> {
-        let config_path = setup::get_config_path();
-        let config_file = File::open(config_path.clone()).expect("Failed to open config file");
-        let config: Config = serde_json::from_reader(config_file)?;
+        Ok(Self::resolve()?.config)
+    }
+
+    // the effective config for renaming files in dir, including the
+    // nearest `.peeksy.toml`, with profile or else the `profile` key applied
+    pub fn fetch_for(dir: &Path, profile: Option<&str>) -> Result<Self, anyhow::Error> {
+        Ok(Self::resolve_for(Some(dir), profile)?.config)
+    }
+
+    pub fn resolve() -> Result<Resolved, anyhow::Error> {
+        Self::resolve_for(None, None)
+    }
+
+    pub fn resolve_for(
+        dir: Option<&Path>,
+        profile: Option<&str>,
+    ) -> Result<Resolved, anyhow::Error> {
+        let mut layers = vec![];
+        let system = files::system_config_path();
+        if system.is_file() {
+            layers.push((Source::System, system.clone(), files::load(&system)?.0));
+        }
+        layers.push((Source::User, setup::get_config_path(), Self::fetch_file()?));
+        if let Some(project) = dir.and_then(files::find_project_config) {
+            let config = files::load_project(&project)?;
+            layers.push((Source::Project, project, config));
+        }
+        layers::resolve(layers, profile)
+    }
+
+    // values that apply when nothing else sets a key
+    pub fn defaults() -> Self {
+        let prompt_path = dirs::config_dir()
+            .unwrap()
+            .join("peeksy")
+            .join("prompt.txt");
+        Self {
+            openai_prompt_file_path: Some(prompt_path.to_string_lossy().to_string()),
+            openai_model: Some("gpt-4o".to_string()),
+            openai_base_url: Some(DEFAULT_OPENAI_BASE_URL.to_string()),
+            catch_up_on_start: Some(true),
+            shutdown_timeout_secs: Some(10),
+            service_restart: Some(RestartPolicy::default()),
+            supervisor_max_restarts: Some(5),
+            supervisor_backoff_secs: Some(1),
+            ..Default::default()
+        }
+    }
+
+    // copies every key that is set in other. maps such as `profiles` are
+    // merged key by key, so files can add profiles without repeating others.
+    pub fn merge(&mut self, other: &Config) {
+        let (Ok(mut base), Ok(overlay)) =
+            (serde_json::to_value(&*self), serde_json::to_value(other))
+        else {
+            return;
+        };
+        merge_values(&mut base, overlay);
+        if let Ok(mut merged) = serde_json::from_value::<Config>(base) {
+            merged.restore_secrets(&[other, self]);
+            *self = merged;
+        }
+    }
+
+    // every place that can hold an api key, by its key path
+    fn secret_slots(&mut self) -> Vec<(String, &mut Option<Secret>)> {
+        let mut slots = vec![(API_KEY_SECRET.to_string(), &mut self.openai_api_key)];
+        for (name, profile) in self.profiles.iter_mut().flatten() {
+            slots.push((profile::api_key_secret(name), &mut profile.openai_api_key));
+        }
+        slots
+    }
+
+    fn secret_at(&self, path: &str) -> Option<Secret> {
+        if path == API_KEY_SECRET {
+            return self.openai_api_key.clone();
+        }
+        self.profiles
+            .iter()
+            .flatten()
+            .find(|(name, _)| profile::api_key_secret(name) == path)
+            .and_then(|(_, profile)| profile.openai_api_key.clone())
+    }
+
+    // secrets are redacted when serialized, so after a round trip through
+    // JSON the ones held in memory are copied back from the originals
+    fn restore_secrets(&mut self, originals: &[&Config]) {
+        for (path, slot) in self.secret_slots() {
+            if !slot.as_ref().is_some_and(Secret::is_redacted) {
+                continue;
+            }
+            let original = originals.iter().find_map(|config| {
+                config
+                    .secret_at(&path)
+                    .filter(|secret| !secret.is_redacted())
+            });
+            if original.is_some() {
+                *slot = original;
+            }
+        }
+    }
+
+    // the config with a profile's keys laid over it. without a name the
+    // `profile` key picks it, and with neither the config is used as is.
+    // flags and environment do not win over it, so effective configs come
+    // from `fetch_for`, which applies the profile as a layer of its own.
+    pub fn with_profile(&self, name: Option<&str>) -> Result<Self, anyhow::Error> {
+        let Some(name) = name.or(self.profile.as_deref()).filter(|n| !n.is_empty()) else {
+            return Ok(self.clone());
+        };
+        let mut config = self.clone();
+        config.merge(&self.profile_layer(name)?);
+        config.profile = Some(name.to_string());
         Ok(config)
     }
 
-    pub fn save(&self) -> Result<(), anyhow::Error> {
-        let config_path = setup::get_config_path();
-        let config_file = File::create(config_path.clone()).expect("Failed to create config file");
-        serde_json::to_writer_pretty(config_file, self)?;
-        Ok(())
+    // the keys the profile name sets, as a config of their own
+    pub fn profile_layer(&self, name: &str) -> Result<Self, anyhow::Error> {
+        let profiles = self.profiles.clone().unwrap_or_default();
+        let profile = profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> = profiles.keys().map(String::as_str).collect();
+            anyhow::anyhow!(
+                "Unknown profile {:?}, known profiles: {}",
+                name,
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            )
+        })?;
+
+        Ok(Config {
+            openai_base_url: profile.openai_base_url.clone(),
+            openai_api_key: profile.openai_api_key.clone(),
+            openai_model: profile.openai_model.clone(),
+            openai_prompt_file_path: profile.openai_prompt_file_path.clone(),
+            openai_system_prompt: profile.openai_system_prompt.clone(),
+            examples_dir: profile.examples_dir.clone(),
+            naming_template: profile.naming_template.clone(),
+            naming_style: profile.naming_style,
+            language: profile.language.clone(),
+            structured_output: profile.structured_output,
+            transliterate: profile.transliterate,
+            destination: profile.destination.clone(),
+            ..Default::default()
+        })
     }
 
-    pub fn ready(&self) -> bool {
-        if !self.openai_api_key_exists() {
-            let err = "[Peeksy Ready] OpenAI API key is not set";
-            error!("{}", err);
-            return false;
+    // directories the daemon watches besides the screenshot directory
+    pub fn watch_profiles(&self) -> Vec<(std::path::PathBuf, String)> {
+        self.watch_profiles
+            .iter()
+            .flatten()
+            .map(|(dir, profile)| (profile::expand_dir(dir), profile.clone()))
+            .collect()
+    }
+
+    // the config file alone, without overrides or defaults
+    pub fn fetch_file() -> Result<Self, anyhow::Error> {
+        Ok(Self::fetch_file_versioned()?.0)
+    }
+
+    // the config file upgraded to the current version, along with the
+    // version it was written as
+    pub fn fetch_file_versioned() -> Result<(Self, u32), anyhow::Error> {
+        files::load(&setup::get_config_path())
+    }
+
+    // writes the config file, moving a plaintext api key into the secret
+    // store first so only a reference to it is written
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let mut config = self.clone();
+        config.version = Some(CURRENT_VERSION);
+        for (path, slot) in config.secret_slots() {
+            if let Some(key) = slot.as_ref().filter(|key| is_plaintext(key)) {
+                *slot = Some(secrets::store(&path, key.expose())?);
+            }
         }
 
-        if !self.openai_prompt_file_path_exists() {
-            let err = "[Peeksy Ready] OpenAI prompt file path is not set";
-            error!("{}", err);
-            return false;
+        files::write(&setup::get_config_path(), &config)
+    }
+
+    // true while the config file still holds the api key itself
+    pub fn has_plaintext_secrets(&self) -> bool {
+        self.clone()
+            .secret_slots()
+            .into_iter()
+            .any(|(_, slot)| slot.as_ref().is_some_and(is_plaintext))
+    }
+
+    // the api key itself, looked up in the secret store when the config
+    // only holds a reference
+    pub fn api_key(&self) -> Result<Secret, anyhow::Error> {
+        let key = self
+            .openai_api_key
+            .as_ref()
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("OpenAI API key is not set"))?;
+        if key.is_redacted() {
+            return Err(anyhow::anyhow!(
+                "OpenAI API key is a redacted placeholder. Set it again with `peeksy edit-config`"
+            ));
         }
+        key.resolve()
+    }
 
-        if !self.openai_model_exists() {
-            let err = "[Peeksy Ready] OpenAI model is not set";
-            error!("{}", err);
+    pub fn ready(&self) -> bool {
+        if let Err(e) = self.validate() {
+            error!("[Peeksy Ready] {}", e);
             return false;
         }
 
         true
     }
 
+    pub fn validate(&self) -> Result<(), Problem> {
+        match validate::check_required(self).into_iter().next() {
+            Some(problem) => Err(problem),
+            None => Ok(()),
+        }
+    }
+
+    pub fn read_prompt(&self) -> Result<String, anyhow::Error> {
+        let path = self
+            .get_openai_prompt_file_path()
+            .ok_or_else(|| anyhow::anyhow!("OpenAI prompt file path is not set"))?;
+        std::fs::read_to_string(&path)
+            .map_err(|e| anyhow::anyhow!("Failed to read prompt file {}: {}", path, e))
+    }
+
     pub fn openai_model_exists(&self) -> bool {
         if let Some(model) = self.openai_model.as_ref() {
             !model.is_empty()
@@ -73,11 +385,10 @@ impl Config {
         }
     }
 
-    pub fn get_openai_api_key(&self) -> Option<String> {
+    pub fn get_openai_api_key(&self) -> Option<Secret> {
         self.openai_api_key.clone()
     }
 
-
     pub fn get_openai_prompt_file_path(&self) -> Option<String> {
         self.openai_prompt_file_path.clone()
     }
@@ -86,6 +397,155 @@ impl Config {
         self.openai_model.clone()
     }
 
+    pub fn get_openai_base_url(&self) -> String {
+        self.openai_base_url
+            .clone()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string())
+    }
+
+    // examples sent with every image, capped so a big directory does not
+    // make every request expensive
+    pub fn max_examples(&self) -> usize {
+        self.max_examples.unwrap_or(examples::DEFAULT_MAX_EXAMPLES)
+    }
+
+    // names asked for per image
+    pub fn candidates(&self) -> usize {
+        self.candidates.unwrap_or(1).max(1) as usize
+    }
+
+    pub fn banned_words(&self) -> Vec<String> {
+        match &self.banned_words {
+            Some(words) => words.iter().map(|word| word.to_lowercase()).collect(),
+            None => score::DEFAULT_BANNED_WORDS
+                .iter()
+                .map(|word| word.to_string())
+                .collect(),
+        }
+    }
+
+    // the built-in prices with the configured ones laid over them
+    pub fn prices(&self) -> BTreeMap<String, Price> {
+        let mut prices = budget::default_prices();
+        prices.extend(self.prices.clone().unwrap_or_default());
+        prices
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget.clone().unwrap_or_default()
+    }
+
+    pub fn cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.cache_ttl_days.unwrap_or(cache::DEFAULT_TTL_DAYS) * 24 * 60 * 60)
+    }
+
+    pub fn cache_max_entries(&self) -> usize {
+        self.cache_max_entries
+            .unwrap_or(cache::DEFAULT_MAX_ENTRIES)
+            .max(1)
+    }
+
+    // the limit for the base url in use, which a profile may change
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        ratelimit::limit_for(self.rate_limits.as_ref()?, &self.get_openai_base_url())
+    }
+
+    // structured output is on unless explicitly disabled in the config
+    pub fn structured_output(&self) -> bool {
+        self.structured_output.unwrap_or(true)
+    }
+
+    // catch-up is on unless explicitly disabled in the config
+    pub fn catch_up_on_start(&self) -> bool {
+        self.catch_up_on_start.unwrap_or(true)
+    }
+
+    // how long a stopping daemon may spend finishing queued renames
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs.unwrap_or(10))
+    }
+
+    // consecutive crashes of the daemon thread tolerated before the daemon exits
+    pub fn supervisor_max_restarts(&self) -> u32 {
+        self.supervisor_max_restarts.unwrap_or(5)
+    }
+
+    // delay before the first restart, doubled on every further crash
+    pub fn supervisor_backoff(&self) -> Duration {
+        Duration::from_secs(self.supervisor_backoff_secs.unwrap_or(1))
+    }
+
+    // looks up a key, nested keys are separated by dots
+    pub fn get_key(&self, key: &str) -> Result<Value, anyhow::Error> {
+        let root = serde_json::to_value(self)?;
+        let mut value = &root;
+        for (depth, part) in key.split('.').enumerate() {
+            value = match value.get(part) {
+                Some(value) => value,
+                // missing entries inside maps are just unset
+                None if depth > 0 && value.is_object() => return Ok(Value::Null),
+                None if depth > 0 && value.is_null() => return Ok(Value::Null),
+                None => return Err(anyhow::anyhow!("Unknown config key: {}", key)),
+            };
+        }
+        Ok(value.clone())
+    }
+
+    // sets a key from its command line form: valid JSON is used as is,
+    // anything else (or JSON of the wrong type) is taken as a string
+    pub fn set_key(&mut self, key: &str, raw: &str) -> Result<(), anyhow::Error> {
+        if let Ok(value) = serde_json::from_str::<Value>(raw) {
+            if self.set_value(key, value).is_ok() {
+                return Ok(());
+            }
+        }
+        self.set_value(key, Value::String(raw.to_string()))
+    }
+
+    pub fn unset_key(&mut self, key: &str) -> Result<(), anyhow::Error> {
+        self.set_value(key, Value::Null)
+    }
+
+    fn set_value(&mut self, key: &str, new_value: Value) -> Result<(), anyhow::Error> {
+        let mut root = serde_json::to_value(&*self)?;
+        let parts: Vec<&str> = key.split('.').collect();
+        let (last, parents) = parts.split_last().unwrap();
+
+        let mut value = &mut root;
+        for (depth, part) in parents.iter().enumerate() {
+            let object = value
+                .as_object_mut()
+                .ok_or_else(|| anyhow::anyhow!("Config key {} is not a map", key))?;
+            if depth == 0 && !object.contains_key(*part) {
+                return Err(anyhow::anyhow!("Unknown config key: {}", key));
+            }
+            let entry = object.entry(part.to_string()).or_insert(Value::Null);
+            if entry.is_null() {
+                *entry = Value::Object(Default::default());
+            }
+            value = entry;
+        }
+
+        let object = value
+            .as_object_mut()
+            .ok_or_else(|| anyhow::anyhow!("Config key {} is not a map", key))?;
+        if parents.is_empty() && !object.contains_key(*last) {
+            return Err(anyhow::anyhow!("Unknown config key: {}", key));
+        }
+        if new_value.is_null() && !parents.is_empty() {
+            object.remove(*last);
+        } else {
+            object.insert(last.to_string(), new_value);
+        }
+
+        let mut updated: Config = serde_json::from_value(root)
+            .map_err(|e| anyhow::anyhow!("Invalid value for {}: {}", key, e))?;
+        updated.restore_secrets(&[self]);
+        *self = updated;
+        Ok(())
+    }
+
     // sets the api key and prompt file path
     pub fn edit_config(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let mut updated = false;
@@ -93,7 +553,7 @@ impl Config {
         // Handle OpenAI API key if empty or not set
         let new_key = self.get_openai_api_key_from_user()?;
         if new_key.is_some() {
-            self.openai_api_key = new_key;
+            self.openai_api_key = new_key.map(Secret::new);
             updated = true;
         }
 
@@ -112,11 +572,51 @@ impl Config {
         }
 
         if updated {
-            let config_clone = self.clone();
             self.save().expect("Failed to save config");
-            info!("[setup_config] Config saved: {:?}", config_clone);
+            info!("[setup_config] Config saved: {:?}", self);
+        }
+
+        Ok(())
+    }
+
+    // creates the profile if needed and asks for each of its keys
+    pub fn edit_profile(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let existing = self.profiles.as_ref().and_then(|p| p.get(name)).cloned();
+        if existing.is_none() {
+            println!("Creating profile {}", name);
+        }
+        println!("Press enter to keep a value, or enter `-` to clear it");
+        let mut profile = existing.unwrap_or_default();
+
+        ask_into("API base URL", &mut profile.openai_base_url)?;
+        let has_key = profile.openai_api_key.is_some();
+        match ask("API key", has_key.then_some("<stored>"))? {
+            Answer::Keep => {}
+            Answer::Clear => profile.openai_api_key = None,
+            Answer::Set(key) => profile.openai_api_key = Some(Secret::new(key)),
         }
+        ask_into("Model", &mut profile.openai_model)?;
+        ask_into("Prompt file path", &mut profile.openai_prompt_file_path)?;
+        ask_into("System prompt", &mut profile.openai_system_prompt)?;
+        ask_into("Examples directory", &mut profile.examples_dir)?;
+        ask_into("Naming template", &mut profile.naming_template)?;
+        ask_into("Language", &mut profile.language)?;
+        ask_into("Destination directory", &mut profile.destination)?;
 
+        self.profiles
+            .get_or_insert_with(Default::default)
+            .insert(name.to_string(), profile.clone());
+
+        if self.profile.as_deref() != Some(name) {
+            if let Answer::Set(answer) = ask("Use it as the default profile? (y/n)", None)? {
+                if answer.eq_ignore_ascii_case("y") {
+                    self.profile = Some(name.to_string());
+                }
+            }
+        }
+
+        self.save().expect("Failed to save config");
+        info!("[setup_config] Profile {} saved: {:?}", name, profile);
         Ok(())
     }
 
@@ -182,3 +682,62 @@ impl Config {
         Ok(Some(input))
     }
 }
+
+fn is_plaintext(key: &Secret) -> bool {
+    !key.is_empty() && !key.is_reference() && !key.is_redacted()
+}
+
+// recursive merge of JSON objects, where set values in overlay win
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                if value.is_null() {
+                    continue;
+                }
+                match base.get_mut(&key) {
+                    Some(existing) if existing.is_object() && value.is_object() => {
+                        merge_values(existing, value)
+                    }
+                    _ => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+enum Answer {
+    Keep,
+    Clear,
+    Set(String),
+}
+
+fn ask(label: &str, current: Option<&str>) -> Result<Answer, anyhow::Error> {
+    match current {
+        Some(current) => println!("{} (current: {}): ", label, current),
+        None => println!("{}: ", label),
+    }
+
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(|e| anyhow::anyhow!("Error reading input: {}", e))?;
+
+    Ok(match input.trim() {
+        "" => Answer::Keep,
+        "-" => Answer::Clear,
+        value => Answer::Set(value.to_string()),
+    })
+}
+
+fn ask_into(label: &str, value: &mut Option<String>) -> Result<(), anyhow::Error> {
+    match ask(label, value.as_deref())? {
+        Answer::Keep => {}
+        Answer::Clear => *value = None,
+        Answer::Set(new_value) => *value = Some(new_value),
+    }
+    Ok(())
+}
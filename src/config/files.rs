@@ -0,0 +1,189 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::config::{config::Config, migrate, validate};
+
+// per-directory config, looked up from the directory of the renamed file
+// upwards
+pub const PROJECT_FILE: &str = ".peeksy.toml";
+
+// a project file comes with whatever directory it sits in, so it may only
+// change how files are named. the api key, base url and service settings
+// must come from the user or system config, otherwise a downloaded folder
+// could send the key to another server.
+pub const PROJECT_KEYS: &[&str] = &[
+    "version",
+    "$schema",
+    "openai_prompt_file_path",
+    "openai_model",
+    "openai_system_prompt",
+    "examples_dir",
+    "max_examples",
+    "naming_template",
+    "naming_style",
+    "transliterate",
+    "language",
+    "banned_words",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+}
+
+impl Format {
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Format::Toml,
+            _ => Format::Json,
+        }
+    }
+}
+
+// shared by every user on the machine, lowest precedence of the files
+pub fn system_config_path() -> PathBuf {
+    match std::env::var_os("PEEKSY_SYSTEM_CONFIG") {
+        Some(path) if !path.is_empty() => PathBuf::from(path),
+        _ => PathBuf::from("/etc/peeksy/peeksy.toml"),
+    }
+}
+
+// the nearest `.peeksy.toml` in dir or one of its parents
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+// the file as a JSON value, whatever its format
+pub fn read_value(path: &Path) -> Result<Value, anyhow::Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open config file {}: {}", path.display(), e))?;
+    match Format::of(path) {
+        Format::Json => serde_json::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("{} is not valid JSON: {}", path.display(), e)),
+        Format::Toml => {
+            let value: toml::Value = toml::from_str(&contents)
+                .map_err(|e| anyhow::anyhow!("{} is not valid TOML: {}", path.display(), e))?;
+            Ok(serde_json::to_value(value)?)
+        }
+    }
+}
+
+// keys a project file may not set
+pub fn check_project(value: &Value) -> Vec<validate::Problem> {
+    value
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| !PROJECT_KEYS.contains(&key.as_str()))
+        .map(|(key, _)| {
+            validate::Problem::new(
+                key,
+                "not allowed in a project config, set it in your user config",
+            )
+        })
+        .collect()
+}
+
+// reads a config file, upgraded to the current version, along with the
+// version it was written as
+pub fn load(path: &Path) -> Result<(Config, u32), anyhow::Error> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        anyhow::anyhow!("Invalid config file {}: {}", path.display(), e)
+    };
+
+    let mut value = read_value(path)?;
+    let version = migrate::migrate(&mut value).map_err(|e| invalid(&e))?;
+
+    let config: Config = serde_json::from_value(value.clone()).map_err(|e| {
+        // name the offending key rather than serde's position
+        match validate::check_file(&value).into_iter().next() {
+            Some(problem) => invalid(&problem),
+            None => invalid(&e),
+        }
+    })?;
+    Ok((config, version))
+}
+
+// like load, for a `.peeksy.toml`. relative paths in it are relative to the
+// directory it sits in, so a project can ship its own prompt file.
+pub fn load_project(path: &Path) -> Result<Config, anyhow::Error> {
+    if let Some(problem) = check_project(&read_value(path)?).into_iter().next() {
+        return Err(anyhow::anyhow!(
+            "Invalid config file {}: {}",
+            path.display(),
+            problem
+        ));
+    }
+
+    let (mut config, _) = load(path)?;
+    if let Some(dir) = path.parent() {
+        for value in [
+            &mut config.openai_prompt_file_path,
+            &mut config.examples_dir,
+            &mut config.destination,
+        ] {
+            if let Some(relative) = value.as_mut() {
+                *relative = dir.join(&*relative).to_string_lossy().to_string();
+            }
+        }
+    }
+    Ok(config)
+}
+
+pub fn write(path: &Path, config: &Config) -> Result<(), anyhow::Error> {
+    let contents = match Format::of(path) {
+        Format::Json => serde_json::to_string_pretty(config)?,
+        Format::Toml => toml_contents(path, config)?,
+    };
+    std::fs::write(path, contents)
+        .map_err(|e| anyhow::anyhow!("Failed to write config file {}: {}", path.display(), e))
+}
+
+// updates the existing document in place, so comments and the order of
+// untouched keys survive `config set`
+fn toml_contents(path: &Path, config: &Config) -> Result<String, anyhow::Error> {
+    let existing = std::fs::read_to_string(path).unwrap_or_default();
+    let mut document: toml_edit::DocumentMut = existing.parse()?;
+    let updated: toml_edit::DocumentMut = toml::to_string(config)?.parse()?;
+
+    let stale: Vec<String> = document
+        .iter()
+        .map(|(key, _)| key.to_string())
+        .filter(|key| !updated.contains_key(key))
+        .collect();
+    for key in stale {
+        document.remove(&key);
+    }
+
+    for (key, item) in updated.iter() {
+        let unchanged = document
+            .get(key)
+            .is_some_and(|current| undecorated(current) == undecorated(item));
+        if !unchanged {
+            document[key] = item.clone();
+        }
+    }
+    Ok(document.to_string())
+}
+
+// an item without its comments and whitespace
+fn undecorated(item: &toml_edit::Item) -> String {
+    match item.as_value() {
+        Some(value) => {
+            let mut value = value.clone();
+            value.decor_mut().clear();
+            value.to_string()
+        }
+        None => item.to_string().trim().to_string(),
+    }
+}
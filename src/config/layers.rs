@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, path::PathBuf, sync::OnceLock};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::config::Config;
+
+// where an effective config value came from, lowest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Default,
+    // the machine-wide file, /etc/peeksy/peeksy.toml
+    System,
+    // the user's file in the config directory, or the one given by --config
+    User,
+    // the profile picked with --profile or the `profile` key
+    Profile,
+    // the nearest .peeksy.toml above the file being renamed
+    Project,
+    Env,
+    Flag,
+}
+
+// short names for the most common keys, on top of PEEKSY_<KEY>
+const ENV_ALIASES: &[(&str, &str)] = &[
+    ("PEEKSY_API_KEY", "openai_api_key"),
+    ("PEEKSY_MODEL", "openai_model"),
+    ("PEEKSY_PROMPT_FILE", "openai_prompt_file_path"),
+    ("PEEKSY_BASE_URL", "openai_base_url"),
+];
+
+// key/value pairs from command line flags
+static FLAG_OVERRIDES: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+pub fn set_flag_overrides(overrides: Vec<(String, String)>) {
+    FLAG_OVERRIDES.set(overrides).ok();
+}
+
+// loads `.env` from the config directory. variables that are already set
+// are never replaced. a `.env` in the working directory is not read, as any
+// folder could then send the api key to another server with PEEKSY_BASE_URL.
+pub fn load_dotenv() {
+    if let Some(config_dir) = dirs::config_dir() {
+        dotenv::from_path(config_dir.join("peeksy").join(".env")).ok();
+    }
+}
+
+// lays the profile name over the config built so far. profiles are only
+// defined in the user and system files, which are already applied.
+fn apply_profile(
+    config: &mut Config,
+    sources: &mut BTreeMap<String, Source>,
+    name: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let Some(name) = name else {
+        return Ok(());
+    };
+    let layer = config.profile_layer(name)?;
+    config.merge(&layer);
+    set_sources(sources, &layer, Source::Profile);
+    config.profile = Some(name.to_string());
+    Ok(())
+}
+
+// environment variables that can set key, the alias taking precedence
+pub fn env_var_names(key: &str) -> Vec<String> {
+    let mut names: Vec<String> = ENV_ALIASES
+        .iter()
+        .filter(|(_, k)| *k == key)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    names.push(format!("PEEKSY_{}", key.to_uppercase()));
+    names
+}
+
+// the config as Peeksy uses it, with the source of every key
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub config: Config,
+    pub sources: BTreeMap<String, Source>,
+    // the config files that were read, lowest precedence first
+    pub files: Vec<(Source, PathBuf)>,
+}
+
+fn keys(config: &Config) -> Vec<String> {
+    match serde_json::to_value(config) {
+        Ok(Value::Object(map)) => map.keys().cloned().collect(),
+        _ => vec![],
+    }
+}
+
+fn set_sources(sources: &mut BTreeMap<String, Source>, config: &Config, source: Source) {
+    if let Ok(Value::Object(map)) = serde_json::to_value(config) {
+        for (key, value) in map {
+            if !value.is_null() {
+                sources.insert(key, source);
+            }
+        }
+    }
+}
+
+// layers flags over environment over the config files over defaults. files
+// are given lowest precedence first. the profile, or else the one the
+// `profile` key names once everything else is applied, goes between the
+// user file and the project file.
+pub fn resolve(
+    files: Vec<(Source, PathBuf, Config)>,
+    profile: Option<&str>,
+) -> Result<Resolved, anyhow::Error> {
+    if let Some(name) = profile.filter(|name| !name.is_empty()) {
+        return resolve_with(&files, Some(name));
+    }
+    let resolved = resolve_with(&files, None)?;
+    match resolved
+        .config
+        .profile
+        .clone()
+        .filter(|name| !name.is_empty())
+    {
+        Some(name) => resolve_with(&files, Some(&name)),
+        None => Ok(resolved),
+    }
+}
+
+fn resolve_with(
+    files: &[(Source, PathBuf, Config)],
+    profile: Option<&str>,
+) -> Result<Resolved, anyhow::Error> {
+    let mut config = Config::defaults();
+    let mut sources = BTreeMap::new();
+    set_sources(&mut sources, &config, Source::Default);
+
+    let mut profile = profile;
+    let mut paths = vec![];
+    for (source, path, file_config) in files {
+        if *source > Source::Profile {
+            apply_profile(&mut config, &mut sources, profile.take())?;
+        }
+        config.merge(file_config);
+        set_sources(&mut sources, file_config, *source);
+        paths.push((*source, path.clone()));
+    }
+    apply_profile(&mut config, &mut sources, profile)?;
+
+    for key in keys(&config) {
+        let found = env_var_names(&key)
+            .into_iter()
+            .find_map(|name| std::env::var(&name).ok().map(|value| (name, value)));
+        if let Some((name, value)) = found {
+            config
+                .set_key(&key, &value)
+                .map_err(|e| anyhow::anyhow!("Invalid value in {}: {}", name, e))?;
+            sources.insert(key, Source::Env);
+        }
+    }
+
+    for (key, value) in FLAG_OVERRIDES.get().into_iter().flatten() {
+        config.set_key(key, value)?;
+        sources.insert(key.clone(), Source::Flag);
+    }
+
+    Ok(Resolved {
+        config,
+        sources,
+        files: paths,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::profile::Profile;
+
+    use super::*;
+
+    #[test]
+    fn flag_beats_profile() {
+        set_flag_overrides(vec![("openai_model".to_string(), "flag-model".to_string())]);
+        let profile = Profile {
+            openai_model: Some("profile-model".to_string()),
+            naming_template: Some("{name}-profile".to_string()),
+            ..Default::default()
+        };
+        let user = Config {
+            profile: Some("work".to_string()),
+            profiles: Some(BTreeMap::from([("work".to_string(), profile)])),
+            ..Default::default()
+        };
+        let project = Config {
+            naming_template: Some("{name}-project".to_string()),
+            ..Default::default()
+        };
+        let files = vec![
+            (Source::User, PathBuf::from("peeksy.toml"), user),
+            (Source::Project, PathBuf::from(".peeksy.toml"), project),
+        ];
+
+        let resolved = resolve(files, None).unwrap();
+        assert_eq!(resolved.config.openai_model.as_deref(), Some("flag-model"));
+        assert_eq!(resolved.sources["openai_model"], Source::Flag);
+        assert_eq!(
+            resolved.config.naming_template.as_deref(),
+            Some("{name}-project")
+        );
+        assert_eq!(resolved.config.profile.as_deref(), Some("work"));
+    }
+
+    #[test]
+    fn dotenv_in_working_directory_is_ignored() {
+        let dir = std::env::temp_dir().join(format!("peeksy-dotenv-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join(".env"),
+            "PEEKSY_BASE_URL=http://attacker.invalid\n",
+        )
+        .unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        load_dotenv();
+        let base_url = std::env::var("PEEKSY_BASE_URL");
+
+        std::env::set_current_dir(cwd).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_ne!(base_url.as_deref(), Ok("http://attacker.invalid"));
+    }
+}
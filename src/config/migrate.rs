@@ -0,0 +1,57 @@
+use serde_json::{Map, Value};
+
+use crate::config::validate::Problem;
+
+// version written by this build. bump it together with a new entry in
+// MIGRATIONS whenever a key is renamed or changes meaning.
+pub const CURRENT_VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>);
+
+// MIGRATIONS[n] upgrades a version n file to version n + 1
+const MIGRATIONS: &[Migration] = &[drop_empty_values];
+
+// version 0 files have no `version` key and were seeded with
+// `"openai_api_key": ""`. empty values now simply mean unset.
+fn drop_empty_values(config: &mut Map<String, Value>) {
+    config.retain(|_, value| !value.is_null() && value.as_str() != Some(""));
+}
+
+pub fn file_version(value: &Value) -> Result<u32, Problem> {
+    let config = value
+        .as_object()
+        .ok_or_else(|| Problem::new("(root)", "config must be a JSON object"))?;
+    match config.get("version") {
+        None | Some(Value::Null) => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| {
+                Problem::new(
+                    "version",
+                    format!("expected a whole number, got {}", version),
+                )
+            }),
+    }
+}
+
+// upgrades value in place to CURRENT_VERSION and returns the version it had
+pub fn migrate(value: &mut Value) -> Result<u32, Problem> {
+    let from = file_version(value)?;
+    if from > CURRENT_VERSION {
+        return Err(Problem::new(
+            "version",
+            format!(
+                "{} is newer than this peeksy supports ({}), please upgrade peeksy",
+                from, CURRENT_VERSION
+            ),
+        ));
+    }
+
+    let config = value.as_object_mut().unwrap();
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(config);
+    }
+    config.insert("version".to_string(), Value::from(CURRENT_VERSION));
+    Ok(from)
+}
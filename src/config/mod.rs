@@ -1,2 +1,9 @@
+pub mod budget;
 pub mod config;
+pub mod files;
+pub mod layers;
+pub mod migrate;
+pub mod profile;
+pub mod secrets;
 pub mod setup;
+pub mod validate;
@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::secrets::Secret, manager::style::NamingStyle};
+
+// a named bundle of naming settings, e.g. "work" against a self-hosted model
+// and "personal" against OpenAI. keys left out fall back to the top-level
+// config.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Profile {
+    #[schemars(description = "Base URL of the OpenAI-compatible API for this profile")]
+    pub openai_base_url: Option<String>,
+    #[schemars(description = "Reference to this profile's API key in the secret store")]
+    pub openai_api_key: Option<Secret>,
+    #[schemars(description = "Ask for a JSON answer, off for servers without structured output")]
+    pub structured_output: Option<bool>,
+    #[schemars(description = "Model used for naming")]
+    pub openai_model: Option<String>,
+    #[schemars(description = "File holding the prompt sent with every image")]
+    pub openai_prompt_file_path: Option<String>,
+    #[schemars(description = "System message sent before the prompt")]
+    pub openai_system_prompt: Option<String>,
+    #[schemars(description = "Directory of example images named the way files should be named")]
+    pub examples_dir: Option<String>,
+    #[schemars(
+        description = "Template for the new file name, e.g. `{date}_{ai}` or `{ai}-{seq:03}`"
+    )]
+    pub naming_template: Option<String>,
+    #[schemars(description = "Case and separators for the words the model suggests")]
+    pub naming_style: Option<NamingStyle>,
+    #[schemars(description = "Language of the suggested names, e.g. `ja` or `de-DE`")]
+    pub language: Option<String>,
+    #[schemars(description = "Transliterate suggested names to ASCII")]
+    pub transliterate: Option<bool>,
+    #[schemars(description = "Directory renamed files are moved to")]
+    pub destination: Option<String>,
+}
+
+// name of a profile's api key in the secret store
+pub fn api_key_secret(profile: &str) -> String {
+    format!("profiles.{}.openai_api_key", profile)
+}
+
+// expands a leading `~/` in a configured directory
+pub fn expand_dir(dir: &str) -> std::path::PathBuf {
+    match (dir.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => std::path::PathBuf::from(dir),
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::OpenOptions,
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use log::warn;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+// config values of the form `secret:<name>` point into the secret store
+pub const REFERENCE_PREFIX: &str = "secret:";
+
+// printed in place of a secret that is held in memory
+pub const REDACTED: &str = "********";
+
+const SERVICE: &str = "peeksy";
+
+// a config value that must never reach logs or stdout. it is either a
+// reference into the secret store, which is safe to show, or the secret
+// itself, e.g. from the environment, which is always redacted.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn is_reference(&self) -> bool {
+        self.0.starts_with(REFERENCE_PREFIX)
+    }
+
+    pub fn is_redacted(&self) -> bool {
+        self.0 == REDACTED
+    }
+
+    // the raw value, only to be used where the secret is sent to the API
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    // the secret itself, looked up in the secret store for references
+    pub fn resolve(&self) -> Result<Secret, anyhow::Error> {
+        match self.0.strip_prefix(REFERENCE_PREFIX) {
+            Some(name) => lookup(name).map(Secret),
+            None => Ok(self.clone()),
+        }
+    }
+
+    fn shown(&self) -> &str {
+        if self.is_empty() || self.is_reference() {
+            &self.0
+        } else {
+            REDACTED
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.shown())
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.shown())
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Secret)
+    }
+}
+
+impl JsonSchema for Secret {
+    fn schema_name() -> String {
+        "Secret".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        String::json_schema(gen)
+    }
+}
+
+// saves value under name and returns the reference to put in the config.
+// the OS secret store is preferred, the encrypted file is the fallback for
+// machines without one, e.g. headless Linux.
+pub fn store(name: &str, value: &str) -> Result<Secret, anyhow::Error> {
+    if let Err(e) = os_store(name, value) {
+        warn!(
+            "OS secret store unavailable, using the encrypted file: {}",
+            e
+        );
+        file_store(name, value)?;
+    }
+    Ok(Secret(format!("{}{}", REFERENCE_PREFIX, name)))
+}
+
+pub fn lookup(name: &str) -> Result<String, anyhow::Error> {
+    if let Ok(value) = os_lookup(name) {
+        return Ok(value);
+    }
+    file_lookup(name)?.ok_or_else(|| {
+        anyhow::anyhow!(
+            "Secret {} not found in the secret store. Set it again with `peeksy edit-config`",
+            name
+        )
+    })
+}
+
+pub fn delete(name: &str) {
+    os_delete(name);
+    if let Ok(mut secrets) = read_file() {
+        if secrets.remove(name).is_some() {
+            write_file(&secrets).ok();
+        }
+    }
+}
+
+fn run(command: &mut Command, stdin: Option<&str>) -> Result<String, anyhow::Error> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut pipe) = child.stdin.take() {
+        if let Some(input) = stdin {
+            pipe.write_all(input.as_bytes())?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .trim_end_matches('\n')
+        .to_string())
+}
+
+// the login keychain, through the `security` tool. the command is written
+// to `security -i` on stdin so the secret never shows up in `ps`.
+#[cfg(target_os = "macos")]
+fn os_store(name: &str, value: &str) -> Result<(), anyhow::Error> {
+    if value.contains(['\n', '\r']) {
+        return Err(anyhow::anyhow!("Secret {} must be a single line", name));
+    }
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    let command = format!(
+        "add-generic-password -U -s {} -a {} -w {}\n",
+        quote(SERVICE),
+        quote(name),
+        quote(value)
+    );
+    run(Command::new("security").arg("-i"), Some(&command))?;
+    // `security -i` exits 0 even when a command fails
+    match os_lookup(name) {
+        Ok(stored) if stored == value => Ok(()),
+        _ => Err(anyhow::anyhow!("The keychain did not store {}", name)),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn os_lookup(name: &str) -> Result<String, anyhow::Error> {
+    run(
+        Command::new("security").args(["find-generic-password", "-s", SERVICE, "-a", name, "-w"]),
+        None,
+    )
+}
+
+#[cfg(target_os = "macos")]
+fn os_delete(name: &str) {
+    run(
+        Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", name]),
+        None,
+    )
+    .ok();
+}
+
+// the Secret Service (GNOME Keyring, KWallet), through libsecret's
+// `secret-tool`, which reads the secret from stdin
+#[cfg(not(target_os = "macos"))]
+fn os_store(name: &str, value: &str) -> Result<(), anyhow::Error> {
+    let label = format!("Peeksy {}", name);
+    run(
+        Command::new("secret-tool").args([
+            "store", "--label", &label, "service", SERVICE, "account", name,
+        ]),
+        Some(value),
+    )
+    .map(|_| ())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn os_lookup(name: &str) -> Result<String, anyhow::Error> {
+    let value = run(
+        Command::new("secret-tool").args(["lookup", "service", SERVICE, "account", name]),
+        None,
+    )?;
+    if value.is_empty() {
+        return Err(anyhow::anyhow!("Secret {} not found", name));
+    }
+    Ok(value)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn os_delete(name: &str) {
+    run(
+        Command::new("secret-tool").args(["clear", "service", SERVICE, "account", name]),
+        None,
+    )
+    .ok();
+}
+
+fn secrets_dir() -> PathBuf {
+    dirs::config_dir().unwrap().join("peeksy")
+}
+
+// ChaCha20-Poly1305 encrypted values, keyed by a random key in a separate
+// owner-only file. this keeps the secret out of the config file, which tends
+// to be shared and backed up, but cannot protect it from the same user.
+fn secrets_path() -> PathBuf {
+    secrets_dir().join("secrets.json")
+}
+
+fn key_path() -> PathBuf {
+    secrets_dir().join("secrets.key")
+}
+
+fn write_private(path: &PathBuf, contents: &[u8]) -> Result<(), anyhow::Error> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+fn cipher(create: bool) -> Result<Option<ChaCha20Poly1305>, anyhow::Error> {
+    let path = key_path();
+    if path.exists() {
+        let key = std::fs::read(&path)?;
+        if key.len() != 32 {
+            return Err(anyhow::anyhow!(
+                "Corrupted secret key file {}",
+                path.display()
+            ));
+        }
+        return Ok(Some(ChaCha20Poly1305::new(Key::from_slice(&key))));
+    }
+    if !create {
+        return Ok(None);
+    }
+    let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+    std::fs::create_dir_all(secrets_dir())?;
+    write_private(&path, &key)?;
+    Ok(Some(ChaCha20Poly1305::new(&key)))
+}
+
+fn read_file() -> Result<BTreeMap<String, String>, anyhow::Error> {
+    match std::fs::read_to_string(secrets_path()) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_file(secrets: &BTreeMap<String, String>) -> Result<(), anyhow::Error> {
+    write_private(
+        &secrets_path(),
+        serde_json::to_string_pretty(secrets)?.as_bytes(),
+    )
+}
+
+fn file_store(name: &str, value: &str) -> Result<(), anyhow::Error> {
+    let cipher = cipher(true)?.unwrap();
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let mut sealed = nonce.to_vec();
+    sealed.extend(
+        cipher
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt secret {}", name))?,
+    );
+
+    let mut secrets = read_file()?;
+    secrets.insert(name.to_string(), STANDARD.encode(sealed));
+    write_file(&secrets)
+}
+
+fn file_lookup(name: &str) -> Result<Option<String>, anyhow::Error> {
+    let Some(sealed) = read_file()?.remove(name) else {
+        return Ok(None);
+    };
+    let Some(cipher) = cipher(false)? else {
+        return Err(anyhow::anyhow!(
+            "Secret key file {} is missing",
+            key_path().display()
+        ));
+    };
+
+    let sealed = STANDARD.decode(sealed)?;
+    if sealed.len() < 12 {
+        return Err(anyhow::anyhow!("Corrupted secret {}", name));
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    let value = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt secret {}", name))?;
+    Ok(Some(String::from_utf8(value)?))
+}
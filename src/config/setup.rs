@@ -1,13 +1,58 @@
-use std::{fs::File, path::PathBuf};
+use std::{path::PathBuf, sync::OnceLock};
 
-use super::config;
+use super::{config, files, migrate};
 
-pub fn get_config_path() -> PathBuf {
+// set from the global `--config` flag
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_config_path(path: PathBuf) {
+    CONFIG_PATH_OVERRIDE.set(absolute(path)).ok();
+}
+
+// made absolute against the directory peeksy started in, so the path can be
+// compared with the ones in file events
+fn absolute(path: PathBuf) -> PathBuf {
+    std::path::absolute(&path).unwrap_or(path)
+}
+
+// peeksy.toml when it exists, the JSON file of older versions otherwise
+pub fn get_default_config_path() -> PathBuf {
     let parent = dirs::config_dir().unwrap().join("peeksy");
+    let toml_path = parent.join("peeksy.toml");
+    if toml_path.exists() {
+        return toml_path;
+    }
     let path: PathBuf = parent.join("peeksy_config.json");
     path
 }
 
+// `--config` flag, then PEEKSY_CONFIG, then the default location
+pub fn get_config_path() -> PathBuf {
+    if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+        return path.clone();
+    }
+    match std::env::var_os("PEEKSY_CONFIG") {
+        Some(path) if !path.is_empty() => absolute(PathBuf::from(path)),
+        _ => get_default_config_path(),
+    }
+}
+
+// the directory of the config in use, which also holds the daemon's pid
+// file, socket, cache and other state, so a peeksy started with its own
+// config never shares them with another one
+pub fn state_dir() -> Result<PathBuf, anyhow::Error> {
+    let path = get_config_path();
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("No config directory on this system"))?
+            .join("peeksy"),
+    };
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", dir.display(), e))?;
+    Ok(dir)
+}
+
 pub fn initial_setup() -> Result<(), Box<anyhow::Error>> {
     initial_prompt_setup()?;
     initial_path_setup()?;
@@ -52,41 +97,48 @@ pub fn initial_path_setup() -> Result<(), Box<anyhow::Error>> {
         }
     }
 
-    let path: PathBuf = parent.join("peeksy_config.json");
+    let path: PathBuf = get_config_path();
+    if let Some(config_dir) = path.parent() {
+        if !config_dir.as_os_str().is_empty() && !config_dir.exists() {
+            std::fs::create_dir_all(config_dir).expect("Failed to create config directory");
+        }
+    }
 
     if !path.exists() {
-        // create an empty json file
-        let file = File::create(path.clone()).expect("Failed to create config file");
-        println!("Created empty config file: {:?}", path);
-
-        // write an empty json object to the file
-        let json = serde_json::json!({
-            "openai_api_key": "",
-
-        });
-
-        // write the json to the file
-        serde_json::to_writer_pretty(file, &json).expect("Failed to write to config file");
-        println!("Wrote empty json object to config file: {:?}", path);
+        // defaults cover every other key
+        let config = config::Config {
+            version: Some(migrate::CURRENT_VERSION),
+            ..Default::default()
+        };
+        files::write(&path, &config).expect("Failed to create config file");
+        eprintln!("Created empty config file: {:?}", path);
     }
 
     Ok(())
 }
 
 fn default_config_setup() -> Result<(), Box<anyhow::Error>> {
-    let mut config = config::Config::fetch()?;
-
-    if !config.openai_prompt_file_path_exists() {
-        let parent = dirs::config_dir().unwrap().join("peeksy");
-        let default_prompt_file_path = parent.join("prompt.txt");
-
-        config.openai_prompt_file_path =
-            Some(default_prompt_file_path.to_str().unwrap().to_string());
+    let (config, version) = config::Config::fetch_file_versioned()?;
+
+    if version < migrate::CURRENT_VERSION {
+        // keep the original around in case the upgrade loses something
+        let path = get_config_path();
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        let backup = path.with_extension(format!("{}.v{}.bak", extension, version));
+        std::fs::copy(&path, &backup).map_err(anyhow::Error::from)?;
         config.save()?;
+        eprintln!(
+            "Upgraded config file {:?} from version {} to {} (backup at {:?})",
+            path,
+            version,
+            migrate::CURRENT_VERSION,
+            backup
+        );
+        return Ok(());
     }
 
-    if !config.openai_model_exists() {
-        config.openai_model = Some("gpt-4o".to_string());
+    // configs from older versions hold the api key in plaintext
+    if config.has_plaintext_secrets() {
         config.save()?;
     }
 
@@ -0,0 +1,292 @@
+use std::{fmt, path::Path};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    config::{config::Config, profile::expand_dir},
+    manager::{naming, prompt},
+};
+
+// every candidate is billed as its own answer
+const MAX_CANDIDATES: u32 = 10;
+
+// one problem with a config key
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    pub key: String,
+    pub reason: String,
+}
+
+impl Problem {
+    pub fn new(key: &str, reason: impl Into<String>) -> Self {
+        Self {
+            key: key.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.reason)
+    }
+}
+
+impl std::error::Error for Problem {}
+
+fn known_keys() -> Vec<String> {
+    schemars::schema_for!(Config)
+        .schema
+        .object
+        .map(|object| object.properties.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+// unknown keys and values of the wrong type in the raw config file
+pub fn check_file(value: &Value) -> Vec<Problem> {
+    let Some(object) = value.as_object() else {
+        return vec![Problem::new("(root)", "config must be a JSON object")];
+    };
+
+    let known = known_keys();
+    let mut problems = vec![];
+    for (key, value) in object {
+        if !known.contains(key) {
+            problems.push(Problem::new(key, "unknown key"));
+            continue;
+        }
+        let single = Value::Object([(key.clone(), value.clone())].into_iter().collect());
+        if let Err(e) = serde_json::from_value::<Config>(single) {
+            problems.push(Problem::new(key, e.to_string()));
+        }
+    }
+    problems
+}
+
+// keys Peeksy cannot rename anything without. cheap enough to run before
+// every rename.
+pub fn check_required(config: &Config) -> Vec<Problem> {
+    let mut problems = vec![];
+    if !config.openai_api_key_exists() {
+        problems.push(Problem::new(
+            "openai_api_key",
+            "not set, run `peeksy edit-config` or set PEEKSY_API_KEY",
+        ));
+    }
+    if !config.openai_prompt_file_path_exists() {
+        problems.push(Problem::new("openai_prompt_file_path", "not set"));
+    }
+    if !config.openai_model_exists() {
+        problems.push(Problem::new("openai_model", "not set"));
+    }
+    problems
+}
+
+// everything check_required covers, plus checks that touch the file system
+// and the secret store, for the config and every profile
+// parts of the prompt file that look like variables but are sent as
+// written, e.g. a misspelled name. they do not fail validation.
+pub fn prompt_warnings(config: &Config) -> Vec<String> {
+    let Some(path) = config
+        .get_openai_prompt_file_path()
+        .filter(|p| !p.is_empty())
+    else {
+        return vec![];
+    };
+    let prompt = std::fs::read_to_string(&path).unwrap_or_default();
+    prompt::check(&prompt)
+        .into_iter()
+        .map(|warning| format!("{}: {}", path, warning))
+        .collect()
+}
+
+pub fn check_config(config: &Config) -> Vec<Problem> {
+    let mut problems = check_settings(config);
+    let profiles = config.profiles.clone().unwrap_or_default();
+
+    if let Some(name) = config.profile.as_ref().filter(|name| !name.is_empty()) {
+        if !profiles.contains_key(name) {
+            problems.push(Problem::new(
+                "profile",
+                format!("unknown profile {:?}", name),
+            ));
+        }
+    }
+    for (dir, name) in config.watch_profiles.iter().flatten() {
+        let key = format!("watch_profiles.{}", dir);
+        if !profiles.contains_key(name) {
+            problems.push(Problem::new(&key, format!("unknown profile {:?}", name)));
+        }
+        if !expand_dir(dir).is_dir() {
+            problems.push(Problem::new(&key, format!("{} is not a directory", dir)));
+        }
+    }
+
+    let budget = config.budget();
+    if let Some(name) = budget.fallback_profile.as_ref() {
+        if !profiles.contains_key(name) {
+            problems.push(Problem::new(
+                "budget.fallback_profile",
+                format!("unknown profile {:?}", name),
+            ));
+        }
+    }
+    for (period, soft, hard) in [
+        ("daily", budget.daily_soft_usd, budget.daily_hard_usd),
+        ("monthly", budget.monthly_soft_usd, budget.monthly_hard_usd),
+    ] {
+        for (kind, cap) in [("soft", soft), ("hard", hard)] {
+            if cap.is_some_and(|cap| cap < 0.0) {
+                let key = format!("budget.{}_{}_usd", period, kind);
+                problems.push(Problem::new(&key, "must not be negative"));
+            }
+        }
+        if let (Some(soft), Some(hard)) = (soft, hard) {
+            if soft > hard {
+                let key = format!("budget.{}_soft_usd", period);
+                problems.push(Problem::new(
+                    &key,
+                    format!("is above the hard cap of {}", hard),
+                ));
+            }
+        }
+    }
+
+    for (url, limit) in config.rate_limits.iter().flatten() {
+        for (name, value) in [
+            ("requests_per_minute", limit.requests_per_minute),
+            ("tokens_per_minute", limit.tokens_per_minute),
+        ] {
+            if value == Some(0) {
+                let key = format!("rate_limits.{}.{}", url, name);
+                problems.push(Problem::new(
+                    &key,
+                    "must be at least 1, unset it for no limit",
+                ));
+            }
+        }
+    }
+
+    // only keys the profile sets itself, the rest was checked above
+    for (name, profile) in &profiles {
+        let Ok(effective) = config.with_profile(Some(name)) else {
+            continue;
+        };
+        let set: Vec<String> = match serde_json::to_value(profile) {
+            Ok(Value::Object(keys)) => keys
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, _)| key)
+                .collect(),
+            _ => vec![],
+        };
+        for problem in check_settings(&effective) {
+            if set.contains(&problem.key) {
+                let key = format!("profiles.{}.{}", name, problem.key);
+                problems.push(Problem::new(&key, problem.reason));
+            }
+        }
+    }
+    problems
+}
+
+fn check_settings(config: &Config) -> Vec<Problem> {
+    let mut problems = check_required(config);
+
+    if config.openai_api_key_exists() {
+        if let Err(e) = config.api_key() {
+            problems.push(Problem::new("openai_api_key", e.to_string()));
+        }
+    }
+
+    if let Some(path) = config
+        .get_openai_prompt_file_path()
+        .filter(|p| !p.is_empty())
+    {
+        match std::fs::read_to_string(&path) {
+            Ok(prompt) if prompt.trim().is_empty() => problems.push(Problem::new(
+                "openai_prompt_file_path",
+                format!("{} is empty", path),
+            )),
+            Ok(_) => {}
+            Err(e) => problems.push(Problem::new(
+                "openai_prompt_file_path",
+                format!("cannot read {}: {}", path, e),
+            )),
+        }
+    }
+
+    if let Some(dir) = config.examples_dir.as_ref().filter(|d| !d.is_empty()) {
+        if !expand_dir(dir).is_dir() {
+            problems.push(Problem::new(
+                "examples_dir",
+                format!("{} is not a directory", dir),
+            ));
+        }
+    }
+
+    if config.max_examples == Some(0) {
+        problems.push(Problem::new(
+            "max_examples",
+            "must be at least 1, unset `examples_dir` to send no examples",
+        ));
+    }
+
+    if let Some(candidates) = config.candidates {
+        if !(1..=MAX_CANDIDATES).contains(&candidates) {
+            problems.push(Problem::new(
+                "candidates",
+                format!("must be from 1 to {}, got {}", MAX_CANDIDATES, candidates),
+            ));
+        }
+    }
+
+    if let Some(url) = &config.openai_base_url {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            problems.push(Problem::new(
+                "openai_base_url",
+                format!("must start with http:// or https://, got {:?}", url),
+            ));
+        }
+    }
+
+    if let Some(template) = &config.naming_template {
+        if template.contains('/') || template.contains('\\') {
+            problems.push(Problem::new(
+                "naming_template",
+                "must not contain path separators, use `destination` to move files",
+            ));
+        }
+        if let Err(e) = naming::check(template) {
+            problems.push(Problem::new("naming_template", e.to_string()));
+        }
+    }
+
+    if let Some(destination) = config.destination.as_ref().filter(|d| !d.is_empty()) {
+        let path = expand_dir(destination);
+        if path.exists() && !path.is_dir() {
+            problems.push(Problem::new(
+                "destination",
+                format!("{} is not a directory", destination),
+            ));
+        }
+    }
+
+    // launchd and systemd resolve relative paths against their own cwd
+    for (key, path) in [
+        ("service_stdout_path", &config.service_stdout_path),
+        ("service_stderr_path", &config.service_stderr_path),
+    ] {
+        if let Some(path) = path {
+            if !Path::new(path).is_absolute() {
+                problems.push(Problem::new(
+                    key,
+                    format!("must be an absolute path, got {:?}", path),
+                ));
+            }
+        }
+    }
+
+    problems
+}
@@ -0,0 +1,30 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::setup::state_dir;
+
+// the checkpoint is the last moment the daemon is known to have been watching
+// the screenshot directory. anything created after it was missed.
+fn get_checkpoint_path() -> Result<PathBuf, anyhow::Error> {
+    Ok(state_dir()?.join("last_seen"))
+}
+
+pub fn save_checkpoint(time: SystemTime) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    let result = get_checkpoint_path()
+        .and_then(|path| std::fs::write(path, secs.to_string()).map_err(anyhow::Error::from));
+    if let Err(e) = result {
+        log::error!("Failed to save checkpoint: {}", e);
+    }
+}
+
+pub fn get_checkpoint() -> Option<SystemTime> {
+    let raw = std::fs::read_to_string(get_checkpoint_path().ok()?).ok()?;
+    let secs = raw.trim().parse::<u64>().ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{mpsc::UnboundedSender, oneshot},
+};
+
+use crate::config::setup::state_dir;
+
+// the daemon listens on a unix socket next to the config, speaking one json
+// object per line in each direction: a `Request` in, a `Response` out.
+pub fn get_socket_path() -> Result<PathBuf, anyhow::Error> {
+    Ok(state_dir()?.join("peeksy.sock"))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Request {
+    Pause,
+    Resume,
+    Reload,
+    Status,
+    Flush,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Status {
+    pub pid: u32,
+    pub uptime_secs: u64,
+    pub paused: bool,
+    // why the daemon paused itself, e.g. a reached budget
+    #[serde(default)]
+    pub paused_reason: Option<String>,
+    pub queue_depth: usize,
+    pub processed: u64,
+    pub skipped: u64,
+    pub failed: u64,
+    pub last_error: Option<String>,
+    pub config_hash: String,
+    pub screenshot_dir: PathBuf,
+    // every watched directory with its profile, screenshot directory first
+    #[serde(default)]
+    pub watched: Vec<(PathBuf, Option<String>)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            status: None,
+        }
+    }
+
+    pub fn status(status: Status) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: Some(status),
+        }
+    }
+}
+
+// a request handed to the daemon loop, which answers through `reply`
+pub struct Message {
+    pub request: Request,
+    pub reply: oneshot::Sender<Response>,
+}
+
+pub fn bind() -> Result<UnixListener, anyhow::Error> {
+    let socket_path = get_socket_path()?;
+    // a socket left behind by a crashed daemon blocks the bind
+    if socket_path.exists() {
+        std::fs::remove_file(&socket_path)?;
+    }
+    let listener = UnixListener::bind(&socket_path)?;
+    info!("Control socket listening on {}", socket_path.display());
+    Ok(listener)
+}
+
+pub fn remove_socket() {
+    if let Ok(socket_path) = get_socket_path() {
+        std::fs::remove_file(socket_path).ok();
+    }
+}
+
+pub async fn serve(listener: UnixListener, tx: UnboundedSender<Message>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                error!("Control socket accept error: {:?}", e);
+                continue;
+            }
+        };
+
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, tx).await {
+                error!("Control socket error: {:?}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: UnixStream,
+    tx: UnboundedSender<Message>,
+) -> Result<(), anyhow::Error> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let (reply, rx) = oneshot::channel();
+                tx.send(Message { request, reply })
+                    .map_err(|_| anyhow::anyhow!("daemon is shutting down"))?;
+                rx.await
+                    .unwrap_or_else(|_| Response::error("daemon is shutting down"))
+            }
+            Err(e) => Response::error(format!("invalid request: {}", e)),
+        };
+
+        let mut out = serde_json::to_string(&response)?;
+        out.push('\n');
+        writer.write_all(out.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+// sends a single request to the running daemon
+pub async fn send(request: Request) -> Result<Response, anyhow::Error> {
+    let stream = UnixStream::connect(get_socket_path()?)
+        .await
+        .map_err(|e| anyhow::anyhow!("Peeksy daemon is not reachable: {}", e))?;
+    let (reader, mut writer) = stream.into_split();
+
+    let mut out = serde_json::to_string(&request)?;
+    out.push('\n');
+    writer.write_all(out.as_bytes()).await?;
+
+    let mut lines = BufReader::new(reader).lines();
+    let line = lines
+        .next_line()
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Peeksy daemon closed the connection"))?;
+    Ok(serde_json::from_str(&line)?)
+}
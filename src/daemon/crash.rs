@@ -0,0 +1,48 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::config::setup::state_dir;
+
+// crashes of the daemon thread, kept across restarts so `peeksy status` can
+// report them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub count: u64,
+    pub last_message: Option<String>,
+    // unix seconds
+    pub last_crash_at: Option<u64>,
+}
+
+fn get_crash_path() -> Result<PathBuf, anyhow::Error> {
+    Ok(state_dir()?.join("crash.json"))
+}
+
+pub fn get_crash_report() -> CrashReport {
+    get_crash_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default()
+}
+
+pub fn record_crash(message: &str) {
+    let mut report = get_crash_report();
+    report.count += 1;
+    report.last_message = Some(message.to_string());
+    report.last_crash_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs());
+
+    let result = serde_json::to_string_pretty(&report)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Ok(std::fs::write(get_crash_path()?, json)?));
+    if let Err(e) = result {
+        error!("Failed to record crash: {}", e);
+    }
+}
@@ -1,7 +1,9 @@
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    mpsc::{channel, RecvTimeoutError},
-    Arc,
+use std::{
+    any::Any,
+    collections::{HashSet, VecDeque},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
 use log::{error, info};
@@ -9,94 +11,663 @@ use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 
 use crate::{
     config,
-    daemon::pid,
-    manager::{ai::OpenAI, image::SSManager},
+    daemon::{
+        checkpoint,
+        control::{self, Request, Response, Status},
+        crash, pending,
+        pid::PidLock,
+        runtime::Runtime,
+    },
+    manager::usage::{self, BudgetExceeded, BudgetState},
     utils::ss::get_screenshot_dir,
 };
 
-use tokio::signal;
+use tokio::{
+    signal::{
+        self,
+        unix::{self, SignalKind},
+    },
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        oneshot, watch, Mutex,
+    },
+    task::JoinError,
+};
+
+const SCREENSHOT_DIR_POLL_INTERVAL: Duration = Duration::from_secs(10);
+const BUDGET_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(200);
+// a daemon thread that survived this long resets the restart backoff
+const STABLE_RUN: Duration = Duration::from_secs(60);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
 
-async fn daemon(shutdown: Arc<AtomicBool>) {
-    let screenshot_dir = get_screenshot_dir();
-    info!("Peeksy is running on {}", screenshot_dir.display());
+// work items fed to the rename pipeline
+#[derive(Debug, Clone)]
+enum Job {
+    // a file reported by the watcher
+    New(PathBuf),
+    // a screenshot taken while the daemon was not running
+    Missed(PathBuf),
+}
 
-    let (tx, rx) = channel();
+fn enqueue_missed_screenshots(
+    runtime: &Runtime,
+    started_at: SystemTime,
+    queued: &HashSet<PathBuf>,
+    tx: &UnboundedSender<Job>,
+) {
+    let since = match checkpoint::get_checkpoint() {
+        Some(since) => since,
+        None => {
+            info!("No checkpoint found, skipping catch-up scan");
+            return;
+        }
+    };
 
-    let mut watcher: RecommendedWatcher =
-        Watcher::new(tx, notify::Config::default()).expect("Failed to create watcher");
-    watcher
-        .watch(&screenshot_dir, RecursiveMode::NonRecursive)
-        .expect("Failed to watch directory");
+    let missed: Vec<PathBuf> = runtime
+        .watched
+        .iter()
+        .flat_map(|watched| {
+            watched
+                .ss_controller
+                .find_missed_screenshots(&watched.dir, since, started_at)
+        })
+        .filter(|path| !queued.contains(path))
+        .collect();
+    info!("Catch-up scan found {} missed screenshots", missed.len());
+    for path in missed {
+        tx.send(Job::Missed(path)).ok();
+    }
+}
 
-    let config = config::config::Config::fetch().expect("Failed to fetch config");
-    let ai = OpenAI::new(
-        config.openai_api_key.unwrap(),
-        config.openai_prompt_file_path.unwrap(),
-        config.openai_model.unwrap(),
-    );
-    let ss_controller = SSManager::new(ai);
+// counters reported over the control socket
+#[derive(Debug, Default)]
+struct Stats {
+    processed: u64,
+    skipped: u64,
+    failed: u64,
+    last_error: Option<String>,
+}
 
-    info!("Setup complete, Peeksy is ready!");
-    while !shutdown.load(Ordering::Relaxed) {
-        match rx.recv_timeout(std::time::Duration::from_millis(100)) {
-            Ok(event) => {
-                if let Ok(Event {
-                    kind: EventKind::Create(_),
-                    paths,
-                    ..
-                }) = event
-                {
-                    for path in paths {
-                        info!("Detected new file: {:?}", path);
-                        let resp = ss_controller.process_new_ss(&path).await;
-                        if let Err(e) = resp {
-                            error!("Error processing file: {:?}", e);
-                        }
-                    }
+// loop state that outlives a single job
+struct State {
+    started_at: Instant,
+    paused: bool,
+    // set when a hard budget paused the daemon, cleared once it resets
+    budget_paused: Option<String>,
+    stats: Stats,
+    // `flush` callers waiting for the queue to drain
+    flush_waiters: Vec<oneshot::Sender<Response>>,
+    // a job has left the queue and is not finished yet
+    in_flight: bool,
+}
+
+impl State {
+    // answers every `flush` caller, e.g. with an error once the daemon
+    // pauses and the queue will not drain
+    fn answer_flush(&mut self, response: impl Fn() -> Response) {
+        for waiter in self.flush_waiters.drain(..) {
+            waiter.send(response()).ok();
+        }
+    }
+}
+
+// the job back when a hard budget stopped it, to be retried once the budget
+// resets
+async fn process_job(runtime: &Runtime, job: Job, stats: &mut Stats) -> Option<Job> {
+    let retry = job.clone();
+    let resp = match job {
+        Job::New(path) => {
+            let ss_controller = runtime.controller_for(&path);
+            // the watcher reports every file, including the ones we create
+            if !ss_controller.is_screenshot_file(&path) {
+                stats.skipped += 1;
+                return None;
+            }
+            info!("Detected new file: {:?}", path);
+            ss_controller.process_new_ss(&path).await
+        }
+        Job::Missed(path) => {
+            info!("Processing missed screenshot: {:?}", path);
+            runtime.controller_for(&path).process_missed_ss(&path).await
+        }
+    };
+    match resp {
+        Ok(_) => stats.processed += 1,
+        Err(e) if e.downcast_ref::<BudgetExceeded>().is_some() => {
+            // a new screenshot is too old for `process_new_ss` by the time
+            // the budget resets
+            return pending_path(runtime, retry).map(Job::Missed);
+        }
+        Err(e) => {
+            error!("Error processing file: {:?}", e);
+            stats.failed += 1;
+            stats.last_error = Some(e.to_string());
+        }
+    }
+    None
+}
+
+// watches the screenshot directory, forwarding new files to the pipeline
+fn screenshot_watcher(tx: UnboundedSender<Job>) -> RecommendedWatcher {
+    Watcher::new(
+        move |event: notify::Result<Event>| match event {
+            Ok(Event {
+                kind: EventKind::Create(_),
+                paths,
+                ..
+            }) => {
+                for path in paths {
+                    tx.send(Job::New(path)).ok();
                 }
             }
-            Err(RecvTimeoutError::Timeout) => continue,
+            Ok(_) => {}
             Err(e) => error!("Watch error: {:?}", e),
+        },
+        notify::Config::default(),
+    )
+    .expect("Failed to create watcher")
+}
+
+// watches the config and prompt files, forwarding every touched path
+fn source_watcher(tx: UnboundedSender<PathBuf>) -> RecommendedWatcher {
+    Watcher::new(
+        move |event: notify::Result<Event>| match event {
+            Ok(Event { kind, paths, .. }) if kind.is_create() || kind.is_modify() => {
+                for path in paths {
+                    tx.send(path).ok();
+                }
+            }
+            Ok(_) => {}
+            Err(e) => error!("Config watch error: {:?}", e),
+        },
+        notify::Config::default(),
+    )
+    .expect("Failed to create config watcher")
+}
+
+fn watch_sources(watcher: &mut RecommendedWatcher, runtime: &Runtime) {
+    for dir in runtime.source_dirs() {
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            error!("Failed to watch {}: {:?}", dir.display(), e);
+        }
+    }
+}
+
+fn unwatch_sources(watcher: &mut RecommendedWatcher, runtime: &Runtime) {
+    for dir in runtime.source_dirs() {
+        watcher.unwatch(&dir).ok();
+    }
+}
+
+// loads a fresh runtime and swaps it in, moving watches as needed.
+// an invalid config is rejected and the current runtime stays active.
+fn reload(
+    runtime: &mut Runtime,
+    ss_watcher: &mut RecommendedWatcher,
+    src_watcher: &mut RecommendedWatcher,
+) -> Result<(), anyhow::Error> {
+    let new_runtime = match Runtime::load() {
+        Ok(new_runtime) => new_runtime,
+        Err(e) => {
+            error!("Rejected config reload, keeping the current config: {}", e);
+            return Err(e);
+        }
+    };
+
+    if new_runtime.same_as(runtime) {
+        return Ok(());
+    }
+
+    let (old_dirs, new_dirs) = (runtime.watch_dirs(), new_runtime.watch_dirs());
+    let added: Vec<&PathBuf> = new_dirs.iter().filter(|d| !old_dirs.contains(d)).collect();
+    for (index, dir) in added.iter().enumerate() {
+        if let Err(e) = ss_watcher.watch(dir, RecursiveMode::NonRecursive) {
+            // leave the watches as they were
+            for dir in &added[..index] {
+                ss_watcher.unwatch(dir).ok();
+            }
+            let err = anyhow::anyhow!("failed to watch {}: {:?}", dir.display(), e);
+            error!("Rejected config reload, {}", err);
+            return Err(err);
+        }
+    }
+    for dir in old_dirs.iter().filter(|d| !new_dirs.contains(d)) {
+        ss_watcher.unwatch(dir).ok();
+    }
+    if new_runtime.screenshot_dir != runtime.screenshot_dir {
+        info!(
+            "Peeksy is now running on {}",
+            new_runtime.screenshot_dir.display()
+        );
+    }
+
+    unwatch_sources(src_watcher, runtime);
+    watch_sources(src_watcher, &new_runtime);
+
+    *runtime = new_runtime;
+    info!("Config reloaded");
+    Ok(())
+}
+
+fn status(state: &State, runtime: &Runtime, queue_depth: usize) -> Status {
+    Status {
+        pid: std::process::id(),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        paused: state.paused,
+        paused_reason: state.budget_paused.clone(),
+        queue_depth,
+        processed: state.stats.processed,
+        skipped: state.stats.skipped,
+        failed: state.stats.failed,
+        last_error: state.stats.last_error.clone(),
+        config_hash: runtime.config_hash(),
+        screenshot_dir: runtime.screenshot_dir.clone(),
+        watched: runtime
+            .watched
+            .iter()
+            .map(|watched| (watched.dir.clone(), watched.profile.clone()))
+            .collect(),
+    }
+}
+
+fn handle_control(
+    message: control::Message,
+    state: &mut State,
+    runtime: &mut Runtime,
+    ss_watcher: &mut RecommendedWatcher,
+    src_watcher: &mut RecommendedWatcher,
+    queue_depth: usize,
+) {
+    info!("Control request: {:?}", message.request);
+    let response = match message.request {
+        Request::Pause => {
+            state.paused = true;
+            state.answer_flush(|| Response::error("daemon was paused before the queue drained"));
+            Response::ok()
+        }
+        Request::Resume => {
+            state.paused = false;
+            state.budget_paused = None;
+            Response::ok()
+        }
+        Request::Reload => match reload(runtime, ss_watcher, src_watcher) {
+            Ok(_) => Response::ok(),
+            Err(e) => Response::error(e.to_string()),
+        },
+        Request::Status => Response::status(status(state, runtime, queue_depth)),
+        Request::Flush => {
+            if queue_depth == 0 && !state.in_flight {
+                Response::ok()
+            } else if state.paused {
+                Response::error(format!(
+                    "daemon is paused with {} queued jobs, resume it first",
+                    queue_depth
+                ))
+            } else {
+                state.flush_waiters.push(message.reply);
+                return;
+            }
+        }
+    };
+    message.reply.send(response).ok();
+}
+
+async fn daemon(
+    mut shutdown: watch::Receiver<bool>,
+    control_rx: Arc<Mutex<UnboundedReceiver<control::Message>>>,
+) -> Result<(), anyhow::Error> {
+    // released on unwind, so a restarted thread can take over the socket
+    let mut control_rx = control_rx.lock().await;
+    let mut runtime =
+        Runtime::load().map_err(|e| anyhow::anyhow!("Failed to load config: {}", e))?;
+    info!("Peeksy is running on {}", runtime.screenshot_dir.display());
+    for watched in &runtime.watched[1..] {
+        info!(
+            "Also watching {} with profile {}",
+            watched.dir.display(),
+            watched.profile.as_deref().unwrap_or("-")
+        );
+    }
+
+    let (tx, mut rx) = unbounded_channel::<Job>();
+    let (source_tx, mut source_rx) = unbounded_channel::<PathBuf>();
+
+    let mut ss_watcher = screenshot_watcher(tx.clone());
+    for dir in runtime.watch_dirs() {
+        ss_watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| anyhow::anyhow!("Failed to watch {}: {:?}", dir.display(), e))?;
+    }
+    let started_at = SystemTime::now();
+
+    let mut src_watcher = source_watcher(source_tx);
+    watch_sources(&mut src_watcher, &runtime);
+
+    // jobs left over from the last shutdown go first
+    let pending: HashSet<PathBuf> = pending::take_pending().into_iter().collect();
+    if !pending.is_empty() {
+        info!("Resuming {} pending screenshots", pending.len());
+    }
+    for path in pending.iter().filter(|path| path.exists()) {
+        tx.send(Job::Missed(path.clone())).ok();
+    }
+
+    // anything created after the watcher started is reported by it, so the
+    // catch-up scan only covers the gap up to `started_at`
+    if runtime.config.catch_up_on_start() {
+        enqueue_missed_screenshots(&runtime, started_at, &pending, &tx);
+    }
+    if rx.is_empty() {
+        checkpoint::save_checkpoint(started_at);
+    }
+
+    // the screenshot location lives outside our config, so it is polled
+    let mut location_check = tokio::time::interval(SCREENSHOT_DIR_POLL_INTERVAL);
+    // a paused daemon resumes on its own when the day or month turns over
+    let mut budget_check = tokio::time::interval(BUDGET_CHECK_INTERVAL);
+
+    // screenshots a hard budget stopped, retried before anything queued
+    // after them once the budget resets
+    let mut held: VecDeque<Job> = VecDeque::new();
+
+    let mut state = State {
+        started_at: Instant::now(),
+        paused: false,
+        budget_paused: None,
+        stats: Stats::default(),
+        flush_waiters: vec![],
+        in_flight: false,
+    };
+
+    info!("Setup complete, Peeksy is ready!");
+    while !*shutdown.borrow() {
+        tokio::select! {
+            job = next_job(&mut held, &mut rx), if !state.paused => {
+                let Some(job) = job else { break };
+                state.in_flight = true;
+                let retry = process_job(&runtime, job, &mut state.stats).await;
+                state.in_flight = false;
+                if let Some(retry) = retry {
+                    let reason = match usage::budget_state(&runtime.config.budget()) {
+                        BudgetState::Hard(reason) => reason,
+                        _ => "budget reached".to_string(),
+                    };
+                    error!("[Budget] {}, pausing until it resets", reason);
+                    state.answer_flush(|| Response::error(format!("daemon paused: {}", reason)));
+                    state.paused = true;
+                    state.budget_paused = Some(reason);
+                    held.push_front(retry);
+                    continue;
+                }
+                if rx.is_empty() && held.is_empty() {
+                    checkpoint::save_checkpoint(SystemTime::now());
+                    state.answer_flush(Response::ok);
+                }
+            }
+            Some(message) = control_rx.recv() => {
+                let queue_depth = rx.len() + held.len();
+                handle_control(
+                    message,
+                    &mut state,
+                    &mut runtime,
+                    &mut ss_watcher,
+                    &mut src_watcher,
+                    queue_depth,
+                );
+            }
+            Some(path) = source_rx.recv() => {
+                if !runtime.is_source(&path) {
+                    continue;
+                }
+                // a single save usually fires several events, settle first
+                tokio::time::sleep(RELOAD_DEBOUNCE).await;
+                while source_rx.try_recv().is_ok() {}
+                info!("Detected change in {}, reloading config", path.display());
+                reload(&mut runtime, &mut ss_watcher, &mut src_watcher).ok();
+            }
+            _ = budget_check.tick(), if state.budget_paused.is_some() => {
+                if !matches!(usage::budget_state(&runtime.config.budget()), BudgetState::Hard(_)) {
+                    info!("[Budget] Budget reset, resuming");
+                    state.paused = false;
+                    state.budget_paused = None;
+                }
+            }
+            _ = location_check.tick() => {
+                if get_screenshot_dir() != runtime.screenshot_dir {
+                    info!("Screenshot location changed, reloading config");
+                    reload(&mut runtime, &mut ss_watcher, &mut src_watcher).ok();
+                }
+            }
+            _ = shutdown.changed() => {}
         }
     }
 
     info!("Shutting down Peeksy thread...");
-    watcher.unwatch(&screenshot_dir).ok();
+    // stop accepting new events before draining what is already queued
+    for dir in runtime.watch_dirs() {
+        ss_watcher.unwatch(&dir).ok();
+    }
+    unwatch_sources(&mut src_watcher, &runtime);
+    drop(ss_watcher);
+
+    let leftover = if state.paused {
+        info!(
+            "Daemon is paused, saving {} queued jobs",
+            rx.len() + held.len()
+        );
+        vec![]
+    } else {
+        drain(&runtime, &mut held, &mut rx, &mut state.stats).await
+    };
+
+    let mut pending: Vec<PathBuf> = leftover
+        .into_iter()
+        .chain(held)
+        .filter_map(|job| pending_path(&runtime, job))
+        .collect();
+    while let Ok(job) = rx.try_recv() {
+        pending.extend(pending_path(&runtime, job));
+    }
+    if !pending.is_empty() {
+        info!(
+            "Saving {} pending screenshots for the next start",
+            pending.len()
+        );
+    }
+    pending::save_pending(&pending);
+    checkpoint::save_checkpoint(SystemTime::now());
+    Ok(())
+}
+
+// the next job, held ones first
+async fn next_job(held: &mut VecDeque<Job>, rx: &mut UnboundedReceiver<Job>) -> Option<Job> {
+    match held.pop_front() {
+        Some(job) => Some(job),
+        None => rx.recv().await,
+    }
+}
+
+// processes held and queued jobs until both are empty or the shutdown
+// deadline passes. a job cut off by the deadline is returned so it can be
+// persisted.
+async fn drain(
+    runtime: &Runtime,
+    held: &mut VecDeque<Job>,
+    rx: &mut UnboundedReceiver<Job>,
+    stats: &mut Stats,
+) -> Vec<Job> {
+    let deadline = tokio::time::Instant::now() + runtime.config.shutdown_timeout();
+    info!("Draining {} queued jobs", rx.len() + held.len());
+
+    let mut leftover = vec![];
+    while let Some(job) = held.pop_front().or_else(|| rx.try_recv().ok()) {
+        let retry = job.clone();
+        let processing = process_job(runtime, job, stats);
+        match tokio::time::timeout_at(deadline, processing).await {
+            Ok(over_budget) => leftover.extend(over_budget),
+            Err(_) => {
+                error!(
+                    "Shutdown deadline reached, {} jobs left",
+                    rx.len() + held.len() + 1
+                );
+                leftover.push(retry);
+                return leftover;
+            }
+        }
+    }
+    leftover
+}
+
+// where a queued job's screenshot will be found on the next start
+fn pending_path(runtime: &Runtime, job: Job) -> Option<PathBuf> {
+    let ss_controller = &runtime.watched[0].ss_controller;
+    match job {
+        Job::New(path) if ss_controller.is_screenshot_file(&path) => {
+            Some(ss_controller.modify_ss_path(&path))
+        }
+        Job::New(_) => None,
+        Job::Missed(path) => Some(path),
+    }
 }
 
-pub async fn run() {
-    let config = config::config::Config::fetch().expect("Failed to fetch config");
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+fn exit_message(result: Result<Result<(), anyhow::Error>, JoinError>) -> String {
+    match result {
+        Ok(Ok(())) => "Peeksy thread exited unexpectedly".to_string(),
+        Ok(Err(e)) => format!("Peeksy thread failed: {}", e),
+        Err(e) if e.is_panic() => {
+            format!("Peeksy thread panicked: {}", panic_message(e.into_panic()))
+        }
+        Err(e) => format!("Peeksy thread was cancelled: {}", e),
+    }
+}
+
+// runs the daemon until a shutdown signal, restarting the daemon thread with
+// exponential backoff when it crashes. gives up with an error after too many
+// consecutive crashes, leaving it to the service manager's restart policy.
+pub async fn run() -> Result<(), anyhow::Error> {
+    let config = config::config::Config::fetch()?;
     if !config.ready() {
         error!("[Peeksy Ready] Please update Peeksy config to use Peeksy. Use `peeksy edit-config` to update Peeksy config");
-        return;
+        return Ok(());
     }
+    let max_restarts = config.supervisor_max_restarts();
+    let initial_backoff = config.supervisor_backoff();
 
     let new_pid = std::process::id();
     info!("Starting Peeksy daemon with PID {}", new_pid);
 
-    // save the pid
-    pid::save_pid(new_pid);
+    // held until run returns, guaranteeing a single daemon
+    let _pid_lock = match PidLock::acquire() {
+        Ok(lock) => lock,
+        Err(e) => {
+            error!("{}", e);
+            return Ok(());
+        }
+    };
 
-    let shutdown = Arc::new(AtomicBool::new(false));
-    let shutdown_clone = shutdown.clone();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (control_tx, control_rx) = unbounded_channel::<control::Message>();
+    match control::bind() {
+        Ok(listener) => {
+            tokio::spawn(control::serve(listener, control_tx.clone()));
+        }
+        Err(e) => error!("Failed to bind control socket: {:?}", e),
+    }
+
+    let control_rx = Arc::new(Mutex::new(control_rx));
+    let spawn_daemon = || {
+        let shutdown_rx = shutdown_rx.clone();
+        let control_rx = control_rx.clone();
+        tokio::spawn(async move {
+            info!("Starting Peeksy thread...");
+            daemon(shutdown_rx, control_rx).await
+        })
+    };
 
-    let peeksy_thread_handler = tokio::spawn(async move {
-        info!("Starting Peeksy thread...");
-        daemon(shutdown_clone).await;
-    });
+    let mut peeksy_thread_handler = Some(spawn_daemon());
+    let mut thread_started_at = Instant::now();
+    let mut restart_at: Option<tokio::time::Instant> = None;
+    let mut restarts = 0;
+    let mut backoff = initial_backoff;
+
+    let mut sigterm = unix::signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    let mut sighup = unix::signal(SignalKind::hangup()).expect("Failed to listen for SIGHUP");
 
     // Wait for shutdown signal
-    tokio::select! {
-        _ = signal::ctrl_c() => {
-            info!("Received shutdown signal. Shutting down...");
-            shutdown.store(true, Ordering::Relaxed);
+    let gave_up = loop {
+        tokio::select! {
+            _ = signal::ctrl_c() => {
+                info!("Received SIGINT. Shutting down...");
+                break false;
+            }
+            _ = sigterm.recv() => {
+                info!("Received SIGTERM. Shutting down...");
+                break false;
+            }
+            _ = sighup.recv() => {
+                info!("Received SIGHUP. Reloading config...");
+                let (reply, _) = oneshot::channel();
+                control_tx
+                    .send(control::Message {
+                        request: Request::Reload,
+                        reply,
+                    })
+                    .ok();
+            }
+            result = async { peeksy_thread_handler.as_mut().unwrap().await }, if peeksy_thread_handler.is_some() => {
+                peeksy_thread_handler = None;
+                let message = exit_message(result);
+                error!("{}", message);
+                crash::record_crash(&message);
+
+                // a thread that ran for a while is not part of a crash loop
+                if thread_started_at.elapsed() > STABLE_RUN {
+                    restarts = 0;
+                    backoff = initial_backoff;
+                }
+                if restarts >= max_restarts {
+                    error!("Peeksy thread crashed {} times in a row, giving up", restarts + 1);
+                    break true;
+                }
+
+                restarts += 1;
+                info!(
+                    "Restarting Peeksy thread in {:?} (attempt {}/{})",
+                    backoff, restarts, max_restarts
+                );
+                restart_at = Some(tokio::time::Instant::now() + backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            _ = tokio::time::sleep_until(restart_at.unwrap_or_else(tokio::time::Instant::now)), if restart_at.is_some() => {
+                restart_at = None;
+                peeksy_thread_handler = Some(spawn_daemon());
+                thread_started_at = Instant::now();
+            }
         }
-        _ = peeksy_thread_handler => {
-            error!("Peeksy thread exited unexpectedly");
+    };
+
+    if let Some(handler) = peeksy_thread_handler {
+        // the thread bounds its own draining by the configured deadline
+        shutdown_tx.send(true).ok();
+        if let Err(e) = handler.await {
+            error!("{}", exit_message(Err(e)));
         }
     }
 
-    // Give the thread a moment to clean up
-    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    control::remove_socket();
     info!("Peeksy: Shutting down");
+
+    if gave_up {
+        return Err(anyhow::anyhow!("Peeksy thread kept crashing"));
+    }
+    Ok(())
 }
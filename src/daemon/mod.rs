@@ -1,2 +1,7 @@
+pub mod checkpoint;
+pub mod control;
+pub mod crash;
 pub mod daemon;
+pub mod pending;
 pub mod pid;
+pub mod runtime;
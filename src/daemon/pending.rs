@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+
+use log::error;
+
+use crate::config::setup::state_dir;
+
+// screenshots that were still queued when the daemon stopped. they are
+// picked up again on the next start.
+fn get_pending_path() -> Result<PathBuf, anyhow::Error> {
+    Ok(state_dir()?.join("pending.json"))
+}
+
+pub fn save_pending(paths: &[PathBuf]) {
+    let pending_path = match get_pending_path() {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to save pending screenshots: {}", e);
+            return;
+        }
+    };
+    if paths.is_empty() {
+        std::fs::remove_file(pending_path).ok();
+        return;
+    }
+
+    let result = serde_json::to_string_pretty(paths)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| std::fs::write(&pending_path, json).map_err(anyhow::Error::from));
+    if let Err(e) = result {
+        error!("Failed to save pending screenshots: {}", e);
+    }
+}
+
+// returns the saved paths and clears the file
+pub fn take_pending() -> Vec<PathBuf> {
+    let pending_path = match get_pending_path() {
+        Ok(path) => path,
+        Err(e) => {
+            error!("Failed to read pending screenshots: {}", e);
+            return vec![];
+        }
+    };
+    let raw = match std::fs::read_to_string(&pending_path) {
+        Ok(raw) => raw,
+        Err(_) => return vec![],
+    };
+    std::fs::remove_file(&pending_path).ok();
+
+    match serde_json::from_str(&raw) {
+        Ok(paths) => paths,
+        Err(e) => {
+            error!("Failed to parse pending screenshots: {}", e);
+            vec![]
+        }
+    }
+}
@@ -1,24 +1,115 @@
-use std::path::PathBuf;
+use std::{io::Write, path::PathBuf};
 
-fn get_pid_path() -> PathBuf {
-    let parent_path = dirs::config_dir().unwrap().join("peeksy");
-    if !parent_path.exists() {
-        std::fs::create_dir_all(parent_path.clone()).unwrap();
+use crate::{config::setup::state_dir, utils::lock::FileLock};
+
+fn get_pid_path() -> Result<PathBuf, anyhow::Error> {
+    Ok(state_dir()?.join("peeksy.pid"))
+}
+
+// lock on the pid file, held for the daemon's lifetime
+pub struct PidLock {
+    lock: FileLock,
+}
+
+impl PidLock {
+    pub fn acquire() -> Result<Self, anyhow::Error> {
+        let pid_path = get_pid_path()?;
+        let Some(lock) = FileLock::try_acquire(&pid_path)? else {
+            return Err(match get_pid() {
+                Ok(pid) => anyhow::anyhow!("Peeksy daemon is already running with PID {}", pid),
+                Err(_) => anyhow::anyhow!("Peeksy daemon is already running"),
+            });
+        };
+
+        let mut file = lock.file();
+        file.set_len(0)?;
+        file.write_all(std::process::id().to_string().as_bytes())?;
+        file.sync_all()?;
+        Ok(Self { lock })
     }
-    parent_path.join("peeksy.pid")
 }
 
-pub fn save_pid(pid: u32) {
-    let pid_path = get_pid_path();
-    std::fs::write(pid_path, pid.to_string()).unwrap();
+impl Drop for PidLock {
+    fn drop(&mut self) {
+        // the pid file is emptied on shutdown, not removed. a daemon starting
+        // now may already have it open, and would lock an unlinked file
+        // while a third one creates a new pid file and runs alongside it.
+        // an empty file reads as not running. the lock is released after
+        // this.
+        self.lock.file().set_len(0).ok();
+    }
 }
 
 pub fn get_pid() -> Result<u32, String> {
-    let pid_path = get_pid_path();
+    let pid_path = get_pid_path().map_err(|e| e.to_string())?;
     if pid_path.exists() {
-        let pid = std::fs::read_to_string(pid_path).unwrap();
-        Ok(pid.parse::<u32>().unwrap())
+        let pid = std::fs::read_to_string(pid_path).map_err(|e| e.to_string())?;
+        // emptied by a daemon that stopped
+        if pid.trim().is_empty() {
+            return Err("Peeksy daemon is not running".to_string());
+        }
+        pid.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Corrupted pid file: {:?}", pid))
     } else {
         Err("Peeksy daemon is not running".to_string())
     }
 }
+
+// only for a daemon known to be stopped, see `PidLock::drop`
+pub fn remove_pid() {
+    if let Ok(pid_path) = get_pid_path() {
+        std::fs::remove_file(pid_path).ok();
+    }
+}
+
+// checks that pid belongs to a running `peeksy daemon`, so a pid reused by
+// an unrelated process is not mistaken for the daemon
+pub fn is_peeksy_daemon(pid: u32) -> bool {
+    let (bin, args) = match process_command(pid) {
+        Some(command) => command,
+        None => return false,
+    };
+
+    let is_peeksy = PathBuf::from(bin)
+        .file_name()
+        .is_some_and(|name| name == "peeksy");
+    is_peeksy && args.iter().any(|arg| arg == "daemon")
+}
+
+// the binary and the arguments pid was started with
+#[cfg(target_os = "linux")]
+fn process_command(pid: u32) -> Option<(String, Vec<String>)> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    let mut args = raw
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).to_string());
+    let bin = args.next()?;
+    Some((bin, args.collect()))
+}
+
+// `ps` gives the command line as one string, so the binary, which may
+// contain spaces as in `Application Support`, is read separately
+#[cfg(not(target_os = "linux"))]
+fn process_command(pid: u32) -> Option<(String, Vec<String>)> {
+    let ps = |field: &str| {
+        let output = std::process::Command::new("ps")
+            .args(["-p", &pid.to_string(), "-o", field])
+            .output()
+            .ok()?;
+        let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        (output.status.success() && !value.is_empty()).then_some(value)
+    };
+    let bin = ps("comm=")?;
+    let command = ps("command=")?;
+    let args = match command.strip_prefix(&bin) {
+        Some(args) => args.split_whitespace().map(str::to_string).collect(),
+        None => command
+            .split_whitespace()
+            .skip(1)
+            .map(str::to_string)
+            .collect(),
+    };
+    Some((bin, args))
+}
@@ -0,0 +1,197 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+};
+
+use log::error;
+
+use crate::{
+    config::{config::Config, files, setup},
+    manager::image::SSManager,
+    utils::ss::get_screenshot_dir,
+};
+
+// a directory the daemon renames screenshots in, with the config that
+// applies there: its `.peeksy.toml` and profile
+#[derive(Debug, Clone)]
+pub struct Watched {
+    pub dir: PathBuf,
+    pub profile: Option<String>,
+    pub config: Config,
+    pub prompt: String,
+    pub ss_controller: SSManager,
+}
+
+impl Watched {
+    fn load(dir: PathBuf, profile: Option<&str>) -> Result<(Self, Vec<PathBuf>), anyhow::Error> {
+        if !dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Directory {} does not exist",
+                dir.display()
+            ));
+        }
+
+        let resolved = Config::resolve_for(Some(&dir), profile)?;
+        let config = resolved.config;
+        let ss_controller = SSManager::from_config(&config)?;
+        let prompt = config.read_prompt()?;
+
+        let mut config_files: Vec<PathBuf> =
+            resolved.files.into_iter().map(|(_, path)| path).collect();
+        let project = dir.join(files::PROJECT_FILE);
+        if !config_files.contains(&project) {
+            config_files.push(project);
+        }
+
+        let watched = Self {
+            dir,
+            profile: config.profile.clone(),
+            config,
+            prompt,
+            ss_controller,
+        };
+        Ok((watched, config_files))
+    }
+
+    fn prompt_path(&self) -> PathBuf {
+        PathBuf::from(
+            self.config
+                .get_openai_prompt_file_path()
+                .unwrap_or_default(),
+        )
+    }
+}
+
+// everything the daemon derives from the config, rebuilt as a whole on reload
+// so a half-applied config is never used
+#[derive(Debug, Clone)]
+pub struct Runtime {
+    // the config of the screenshot directory, for daemon-wide settings
+    pub config: Config,
+    pub screenshot_dir: PathBuf,
+    // the screenshot directory first, then `watch_profiles`
+    pub watched: Vec<Watched>,
+    // config files that apply to any watched directory, including a
+    // `.peeksy.toml` that does not exist yet
+    pub config_files: Vec<PathBuf>,
+}
+
+impl Runtime {
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let screenshot_dir = get_screenshot_dir();
+        if !screenshot_dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Screenshot directory {} does not exist",
+                screenshot_dir.display()
+            ));
+        }
+
+        let base = Config::fetch_for(&screenshot_dir, None)?;
+        let mut dirs: Vec<(PathBuf, Option<String>)> = vec![(screenshot_dir.clone(), None)];
+        for (dir, profile) in base.watch_profiles() {
+            match dirs.iter_mut().find(|(existing, _)| *existing == dir) {
+                // the screenshot directory can be given a profile too
+                Some(entry) => entry.1 = Some(profile),
+                None => dirs.push((dir, Some(profile))),
+            }
+        }
+
+        let mut watched = vec![];
+        let mut config_files = vec![];
+        for (index, (dir, profile)) in dirs.into_iter().enumerate() {
+            // an unplugged drive should not take the daemon down with it
+            if index > 0 && !dir.is_dir() {
+                error!(
+                    "Skipping watched directory {}, it does not exist",
+                    dir.display()
+                );
+                continue;
+            }
+            let (entry, files) = Watched::load(dir.clone(), profile.as_deref())
+                .map_err(|e| anyhow::anyhow!("{}: {}", dir.display(), e))?;
+            watched.push(entry);
+            for file in files {
+                if !config_files.contains(&file) {
+                    config_files.push(file);
+                }
+            }
+        }
+
+        Ok(Self {
+            config: watched[0].config.clone(),
+            screenshot_dir,
+            watched,
+            config_files,
+        })
+    }
+
+    pub fn watch_dirs(&self) -> Vec<PathBuf> {
+        self.watched
+            .iter()
+            .map(|watched| watched.dir.clone())
+            .collect()
+    }
+
+    // the manager for the watched directory a file belongs to
+    pub fn controller_for(&self, path: &Path) -> &SSManager {
+        let parent = path.parent().unwrap_or(Path::new(""));
+        &self
+            .watched
+            .iter()
+            .find(|watched| watched.dir == parent)
+            .unwrap_or(&self.watched[0])
+            .ss_controller
+    }
+
+    // files whose changes should trigger a reload
+    pub fn is_source(&self, path: &Path) -> bool {
+        path == setup::get_config_path()
+            || self.config_files.iter().any(|file| file == path)
+            || self
+                .watched
+                .iter()
+                .any(|watched| watched.prompt_path() == path)
+    }
+
+    // directories to watch for source changes. editors often replace files
+    // instead of writing in place, so the parent is watched rather than the file.
+    pub fn source_dirs(&self) -> Vec<PathBuf> {
+        let mut paths = self.config_files.clone();
+        paths.push(setup::get_config_path());
+        paths.extend(self.watched.iter().map(Watched::prompt_path));
+
+        let mut dirs = vec![];
+        for path in paths {
+            if let Some(parent) = path.parent() {
+                if !dirs.iter().any(|d: &PathBuf| d == parent) {
+                    dirs.push(parent.to_path_buf());
+                }
+            }
+        }
+        dirs
+    }
+
+    // short fingerprint of the active configs and prompts, reported by `status`
+    pub fn config_hash(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        for watched in &self.watched {
+            watched.dir.hash(&mut hasher);
+            serde_json::to_string(&watched.config)
+                .unwrap_or_default()
+                .hash(&mut hasher);
+            watched.prompt.hash(&mut hasher);
+        }
+        format!("{:016x}", hasher.finish())
+    }
+
+    pub fn same_as(&self, other: &Runtime) -> bool {
+        self.screenshot_dir == other.screenshot_dir
+            && self.watched.len() == other.watched.len()
+            && self
+                .watched
+                .iter()
+                .zip(&other.watched)
+                .all(|(a, b)| a.dir == b.dir && a.config == b.config && a.prompt == b.prompt)
+    }
+}
@@ -2,56 +2,104 @@ use std::{path::PathBuf, process::Command};
 
 use log::info;
 
-const PLIST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
-        <!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-        <plist version="1.0">
-        <dict>
-            <key>Label</key>
-            <string>com.anubhavitis.peeksy</string>
-            <key>ProgramArguments</key>
-            <array>
-                <string>/opt/homebrew/bin/peeksy</string>
-                <string>daemon</string>
-            </array>
-            <key>RunAtLoad</key>
-            <true/>
-            <key>KeepAlive</key>
-            <false/>
-            <key>StandardOutPath</key>
-            <string>/tmp/peeksy.out</string>
-            <key>StandardErrorPath</key>
-            <string>/tmp/peeksy.err</string>
-        </dict>
-        </plist>"#;
+use crate::service::service::{RestartPolicy, ServiceDefinition, ServiceManager};
+
+const LABEL: &str = "com.anubhavitis.peeksy";
+
+fn escape_xml(raw: &str) -> String {
+    raw.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn keep_alive(restart: RestartPolicy) -> &'static str {
+    match restart {
+        RestartPolicy::Never => "<false/>",
+        // only restart when the daemon did not exit cleanly
+        RestartPolicy::OnFailure => {
+            "<dict>\n        <key>SuccessfulExit</key>\n        <false/>\n    </dict>"
+        }
+        RestartPolicy::Always => "<true/>",
+    }
+}
+
+// renders the launchd plist running `<exe> daemon`
+pub fn plist_file(definition: &ServiceDefinition) -> String {
+    let log_dir = dirs::config_dir().unwrap().join("peeksy");
+    let stdout_path = definition
+        .stdout_path
+        .clone()
+        .unwrap_or_else(|| log_dir.join("peeksy.out"));
+    let stderr_path = definition
+        .stderr_path
+        .clone()
+        .unwrap_or_else(|| log_dir.join("peeksy.err"));
+
+    let mut environment = String::new();
+    if !definition.environment.is_empty() {
+        environment.push_str("    <key>EnvironmentVariables</key>\n    <dict>\n");
+        for (key, value) in &definition.environment {
+            environment.push_str(&format!(
+                "        <key>{}</key>\n        <string>{}</string>\n",
+                escape_xml(key),
+                escape_xml(value)
+            ));
+        }
+        environment.push_str("    </dict>\n");
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>daemon</string>
+    </array>
+{}    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    {}
+    <key>StandardOutPath</key>
+    <string>{}</string>
+    <key>StandardErrorPath</key>
+    <string>{}</string>
+</dict>
+</plist>
+"#,
+        LABEL,
+        escape_xml(&definition.exe.to_string_lossy()),
+        environment,
+        keep_alive(definition.restart),
+        escape_xml(&stdout_path.to_string_lossy()),
+        escape_xml(&stderr_path.to_string_lossy()),
+    )
+}
 
 pub struct LaunchD {
     plist_path: PathBuf,
 }
 
+impl Default for LaunchD {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LaunchD {
     pub fn new() -> Self {
         let user_home_dir_path = dirs::home_dir().unwrap();
-        let plist_path =
-            user_home_dir_path.join("Library/LaunchAgents/com.anubhavitis.peeksy.plist");
+        let plist_path = user_home_dir_path.join(format!("Library/LaunchAgents/{}.plist", LABEL));
 
-        let launchd = Self { plist_path };
-        launchd.setup();
-        launchd.write_plist();
-        launchd
+        Self { plist_path }
     }
 
-    fn setup(&self) {
-        let plist_dir = self.plist_path.parent().unwrap();
-        if !plist_dir.exists() {
-            std::fs::create_dir_all(plist_dir).unwrap();
-        }
-    }
-
-    fn write_plist(&self) {
-        std::fs::write(self.plist_path.clone(), PLIST).unwrap();
-    }
-
-    async fn status(&self) -> Result<i32, anyhow::Error> {
+    fn status(&self) -> Result<i32, anyhow::Error> {
         info!("checking LaunchD plist status");
         let output = Command::new("launchctl").args(["list"]).output().unwrap();
 
@@ -61,9 +109,7 @@ impl LaunchD {
         // if PID > 0 the process is running, else not.
 
         let output_str = String::from_utf8(output.stdout).unwrap();
-        let mut lines = output_str
-            .lines()
-            .filter(|line| line.contains("com.anubhavitis.peeksy"));
+        let mut lines = output_str.lines().filter(|line| line.contains(LABEL));
 
         // if no lines
         let line = lines.next();
@@ -80,31 +126,42 @@ impl LaunchD {
             None => Err(anyhow::anyhow!("LaunchD plist is not running")),
         }
     }
+}
 
-    pub async fn is_loaded(&self) -> bool {
-        match self.status().await {
-            Ok(_) => true,
-            Err(_) => false,
-        }
+impl ServiceManager for LaunchD {
+    fn name(&self) -> &'static str {
+        "launchd"
+    }
+
+    fn definition_path(&self) -> PathBuf {
+        self.plist_path.clone()
+    }
+
+    fn render(&self, definition: &ServiceDefinition) -> String {
+        plist_file(definition)
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.status().is_ok()
     }
 
-    pub async fn is_running(&self) -> bool {
-        match self.status().await {
+    fn is_running(&self) -> bool {
+        match self.status() {
             Ok(pid) => pid > 0,
             Err(_) => false,
         }
     }
 
-    pub async fn load(&self) {
-        if self.is_loaded().await {
+    fn load(&self) {
+        if self.is_loaded() {
             // if loaded, but not running, then unload first.
-            if self.is_running().await {
+            if self.is_running() {
                 info!("LaunchD plist is already running");
                 return;
             }
 
             info!("LaunchD plist is already loaded, but not running. Reloading...");
-            self.unload().await;
+            self.unload();
             return;
         }
 
@@ -116,8 +173,8 @@ impl LaunchD {
         info!("LaunchD plist loaded successfully: {}", output.status);
     }
 
-    pub async fn unload(&self) {
-        if !self.is_loaded().await {
+    fn unload(&self) {
+        if !self.is_loaded() {
             info!("LaunchD plist is not running");
             return;
         }
@@ -1,7 +1,11 @@
+#![allow(clippy::module_inception)]
+
 pub mod cli;
 pub mod config;
 pub mod daemon;
 pub mod launchd;
 pub mod logger;
 pub mod manager;
+pub mod service;
+pub mod systemd;
 pub mod utils;
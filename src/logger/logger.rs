@@ -16,7 +16,6 @@ fn get_log_path() -> PathBuf {
 fn get_info_writable(log_path: PathBuf) -> File {
     let info_path = log_path.join("info.log");
     let info_writable = OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(&info_path)
@@ -27,7 +26,6 @@ fn get_info_writable(log_path: PathBuf) -> File {
 fn get_error_writable(log_path: PathBuf) -> File {
     let error_path = log_path.join("error.log");
     let error_writable = OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(&error_path)
@@ -38,7 +36,6 @@ fn get_error_writable(log_path: PathBuf) -> File {
 fn get_debug_writable(log_path: PathBuf) -> File {
     let debug_path = log_path.join("debug.log");
     let debug_writable = OpenOptions::new()
-        .write(true)
         .create(true)
         .append(true)
         .open(&debug_path)
@@ -46,6 +43,34 @@ fn get_debug_writable(log_path: PathBuf) -> File {
     debug_writable
 }
 
+// systemd sets JOURNAL_STREAM when stdout/stderr are connected to journald
+fn is_journald() -> bool {
+    std::env::var_os("JOURNAL_STREAM").is_some()
+}
+
+fn get_term_logger() -> Box<TermLogger> {
+    if is_journald() {
+        // journald adds its own timestamps and does not render colors
+        let config = ConfigBuilder::new()
+            .set_time_level(LevelFilter::Off)
+            .set_target_level(LevelFilter::Off)
+            .build();
+        return TermLogger::new(
+            LevelFilter::Info,
+            config,
+            TerminalMode::Mixed,
+            ColorChoice::Never,
+        );
+    }
+
+    TermLogger::new(
+        LevelFilter::Info,
+        Config::default(),
+        TerminalMode::Mixed,
+        ColorChoice::Auto,
+    )
+}
+
 pub fn setup_logger() {
     let log_path = get_log_path();
     let info_writable = get_info_writable(log_path.clone());
@@ -53,12 +78,7 @@ pub fn setup_logger() {
     let debug_writable = get_debug_writable(log_path.clone());
 
     let mut loggers: Vec<Box<dyn SharedLogger>> = vec![
-        TermLogger::new(
-            LevelFilter::Info,
-            Config::default(),
-            TerminalMode::Mixed,
-            ColorChoice::Auto,
-        ),
+        get_term_logger(),
         WriteLogger::new(LevelFilter::Error, Config::default(), error_writable),
     ];
 
@@ -1,14 +1,23 @@
 use clap::Parser;
-use peeksy::{cli, config, launchd, logger::logger};
+use peeksy::{cli, config, logger::logger};
 
 #[tokio::main]
 async fn main() {
+    let args = cli::cli::Args::parse();
+    config::layers::load_dotenv();
+    if let Some(path) = args.config_path() {
+        config::setup::set_config_path(path);
+    }
+    config::layers::set_flag_overrides(args.config_overrides());
+
     // initial setups
     logger::setup_logger();
-    config::setup::initial_setup().expect("Failed to setup config");
-    launchd::launchd::LaunchD::new();
+    // a broken config file is reported by the command that reads it, so
+    // `peeksy config validate` still gets to explain what is wrong
+    if let Err(e) = config::setup::initial_setup() {
+        eprintln!("⚠️ {}", e);
+    }
 
     // check_permissions();
-    let args = cli::cli::Args::parse();
     args.execute().await;
 }
@@ -1,87 +1,591 @@
 #![allow(deprecated)]
-use log::info;
+use log::{info, warn};
 use reqwest::header::{AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
 use serde_json::json;
-use std::{fs::File, io::Read, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Read,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::{
+    config::{
+        budget::{self, Price},
+        profile::expand_dir,
+    },
+    config::{config::Config, secrets::Secret},
+    manager::{
+        cache,
+        examples::{self, Example},
+        naming::{self, Suggestion},
+        prompt::{self, PromptContext},
+        ratelimit::{self, RateLimit},
+        usage,
+    },
+};
+
+// used when the config has no `openai_system_prompt`. the naming style is
+// left to the prompt file.
+pub const DEFAULT_SYSTEM_PROMPT: &str = "You are a filename generation bot. You must return only a filename for the attached image, following the user's instructions. No explanations. No descriptions. No quotes. No code blocks. Just the filename.";
+
+// added to the system prompt when the answer is structured
+const STRUCTURED_INSTRUCTIONS: &str = "Answer in JSON: `filename` is the filename, `category` a one or two word category for the image, `app` the application shown or an empty string, `tags` up to five keywords, `description` one sentence about the image and `contains_sensitive` whether it shows passwords, keys, personal or financial data.";
+
+// answered when the model gives nothing usable, never cached
+const UNKNOWN_NAME: &str = "unknown-name";
+
+// the structured answer, checked by serde so a malformed one falls back to
+// reading the text
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NameResponse {
+    filename: String,
+    category: String,
+    app: String,
+    tags: Vec<String>,
+    description: String,
+    contains_sensitive: bool,
+}
+
+impl NameResponse {
+    fn schema() -> serde_json::Value {
+        json!({
+            "name": "filename",
+            "strict": true,
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "filename": { "type": "string" },
+                    "category": { "type": "string" },
+                    "app": { "type": "string" },
+                    "tags": { "type": "array", "items": { "type": "string" } },
+                    "description": { "type": "string" },
+                    "contains_sensitive": { "type": "boolean" }
+                },
+                "required": ["filename", "category", "app", "tags", "description", "contains_sensitive"],
+                "additionalProperties": false
+            }
+        })
+    }
+
+    fn into_suggestion(self) -> Option<Suggestion> {
+        let name = self.filename.trim().to_string();
+        if name.is_empty() {
+            return None;
+        }
+        let mut suggestion = Suggestion {
+            name,
+            tags: self.tags,
+            description: Some(self.description).filter(|d| !d.trim().is_empty()),
+            contains_sensitive: self.contains_sensitive,
+            ..Default::default()
+        };
+        for (field, value) in [("category", self.category), ("app", self.app)] {
+            let value = value.split_whitespace().collect::<Vec<_>>().join("-");
+            if !value.is_empty() {
+                suggestion.fields.insert(field.to_string(), value);
+            }
+        }
+        Some(suggestion)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct OpenAI {
-    api_key: String,
+    api_key: Secret,
     prompt: String,
+    system_prompt: String,
+    examples: Vec<Example>,
     model: String,
+    base_url: String,
+    // named in rendering errors
+    prompt_path: String,
+    profile: Option<String>,
+    language: Option<String>,
+    // cleared once the server turns down structured output, so the rest of
+    // the run does not pay for a rejected request every time
+    structured: Arc<AtomicBool>,
+    // for the cost of each request in the usage records
+    prices: BTreeMap<String, Price>,
+    // how long answers are reused for the same image, zero turns it off
+    cache_ttl: Duration,
+    cache_max_entries: usize,
+    // shared with every other process using the same base url
+    rate_limit: Option<RateLimit>,
 }
 
 impl OpenAI {
-    pub fn new(api_key: String, prompt: String, model: String) -> Self {
+    pub fn new(api_key: Secret, prompt: String, model: String, base_url: String) -> Self {
         Self {
             api_key,
             prompt,
+            system_prompt: DEFAULT_SYSTEM_PROMPT.to_string(),
+            examples: vec![],
             model,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            prompt_path: String::new(),
+            profile: None,
+            language: None,
+            structured: Arc::new(AtomicBool::new(true)),
+            prices: budget::default_prices(),
+            cache_ttl: Duration::from_secs(cache::DEFAULT_TTL_DAYS * 24 * 60 * 60),
+            cache_max_entries: cache::DEFAULT_MAX_ENTRIES,
+            rate_limit: None,
+        }
+    }
+
+    // builds a client from a validated config, reading the prompt file
+    pub fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
+        config.validate()?;
+        let api_key = config.api_key()?;
+        let prompt = config.read_prompt()?;
+        let prompt_path = config.get_openai_prompt_file_path().unwrap_or_default();
+        for warning in prompt::check(&prompt) {
+            warn!("Prompt file {}: {}", prompt_path, warning);
+        }
+
+        let mut ai = Self::new(
+            api_key,
+            prompt,
+            config.openai_model.clone().unwrap(),
+            config.get_openai_base_url(),
+        );
+        ai.prompt_path = prompt_path;
+        if let Some(system_prompt) = config
+            .openai_system_prompt
+            .as_ref()
+            .filter(|p| !p.trim().is_empty())
+        {
+            ai.system_prompt = system_prompt.clone();
         }
+        // decoded once here rather than for every image
+        if let Some(dir) = config.examples_dir.as_ref().filter(|d| !d.is_empty()) {
+            ai.examples = examples::load(&expand_dir(dir), config.max_examples())?;
+        }
+        ai.profile = config.profile.clone();
+        ai.language = config.language.clone().filter(|l| !l.trim().is_empty());
+        ai.structured = Arc::new(AtomicBool::new(config.structured_output()));
+        ai.prices = config.prices();
+        ai.cache_ttl = config.cache_ttl();
+        ai.cache_max_entries = config.cache_max_entries();
+        ai.rate_limit = config.rate_limit();
+        Ok(ai)
+    }
+
+    // a client that always asks the model, e.g. to check that it answers
+    pub fn without_cache(mut self) -> Self {
+        self.cache_ttl = Duration::ZERO;
+        self
     }
 
-    pub async fn get_name(&self, image_path: &PathBuf) -> String {
+    // the model when its requests have no known cost, so they never count
+    // against a budget
+    pub fn unpriced_model(&self) -> Option<&str> {
+        budget::price_for(&self.prices, &self.model)
+            .is_none()
+            .then_some(self.model.as_str())
+    }
+
+    // cheap authenticated request, used by `peeksy doctor` to verify the key
+    pub async fn check_auth(&self) -> Result<(), anyhow::Error> {
+        let response = reqwest::Client::new()
+            .get(format!("{}/models", self.base_url))
+            .header(AUTHORIZATION, format!("Bearer {}", self.api_key.expose()))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(anyhow::anyhow!("API returned {}", response.status()));
+        }
+        Ok(())
+    }
+
+    // count names for an image, along with the naming fields in `fields`
+    // that the model provides, e.g. `app`
+    pub async fn get_names(
+        &self,
+        image_path: &PathBuf,
+        fields: &[&str],
+        count: usize,
+    ) -> Result<Vec<Suggestion>, anyhow::Error> {
         info!("Getting name for image: {:?}", image_path.display());
+        let context = PromptContext {
+            image: image_path,
+            profile: self.profile.as_deref(),
+            locale: self.language.as_deref(),
+        };
+        let prompt = prompt::render(&self.prompt, &context).map_err(|e| {
+            anyhow::anyhow!("Failed to render prompt file {}: {}", self.prompt_path, e)
+        })?;
+
+        let key = self.cache_key(image_path, &prompt, fields, count);
+        if let Some(key) = key.as_ref() {
+            if let Some(suggestions) = cache::get(key, self.cache_ttl).await {
+                info!("Using the cached name for {:?}", image_path.display());
+                return Ok(suggestions);
+            }
+        }
+        let suggestions = self.ask(image_path, prompt, fields, count).await?;
+        if let Some(key) = key {
+            if suggestions.iter().any(|s| s.name != UNKNOWN_NAME) {
+                cache::put(&key, &suggestions, self.cache_ttl, self.cache_max_entries).await;
+            }
+        }
+        Ok(suggestions)
+    }
+
+    // the cache key for a request, None when the cache is off. everything
+    // sent besides the image goes into the prompt part of the key.
+    fn cache_key(
+        &self,
+        image_path: &PathBuf,
+        prompt: &str,
+        fields: &[&str],
+        count: usize,
+    ) -> Option<String> {
+        if self.cache_ttl.is_zero() {
+            return None;
+        }
+        let image = std::fs::read(image_path).ok()?;
+        let mut request = vec![
+            self.system_prompt.clone(),
+            self.language.clone().unwrap_or_default(),
+            prompt.to_string(),
+            fields.join(","),
+            count.to_string(),
+        ];
+        for example in &self.examples {
+            request.push(example.name.clone());
+            request.push(example.image_url.clone());
+        }
+        Some(cache::key(
+            &image,
+            &self.base_url,
+            &self.model,
+            &request.join("\n"),
+        ))
+    }
+
+    async fn ask(
+        &self,
+        image_path: &PathBuf,
+        prompt: String,
+        fields: &[&str],
+        count: usize,
+    ) -> Result<Vec<Suggestion>, anyhow::Error> {
+        if self.structured.load(Ordering::Relaxed) {
+            let messages = self.messages(image_path, &prompt, true)?;
+            match self.make_ai_request(&messages, true, count).await {
+                Ok(answers) => {
+                    let suggestions: Vec<Suggestion> = answers
+                        .iter()
+                        .filter_map(|answer| {
+                            match serde_json::from_str::<NameResponse>(answer) {
+                                Ok(response) => response.into_suggestion(),
+                                // a model that ignores the schema usually
+                                // still names the file
+                                Err(e) => {
+                                    warn!(
+                                        "[Structured Output] Invalid answer, reading it as text: {}",
+                                        e
+                                    );
+                                    Some(parse_suggestion(answer, fields))
+                                }
+                            }
+                        })
+                        .collect();
+                    if !suggestions.is_empty() {
+                        return Ok(suggestions);
+                    }
+                    warn!("[Structured Output] Empty filename, asking for plain text");
+                }
+                Err(RequestError::Unsupported(e)) => {
+                    warn!(
+                        "[Structured Output] Not supported by {}, using plain text: {}",
+                        self.base_url, e
+                    );
+                    self.structured.store(false, Ordering::Relaxed);
+                }
+                Err(RequestError::Other(e)) => return Err(e),
+            }
+        }
+
+        // plain text: the name on the first line, then the fields the
+        // template needs one per line
+        let mut prompt = prompt;
+        let asked: Vec<&(&str, &str)> = naming::AI_FIELDS
+            .iter()
+            .filter(|(name, _)| fields.contains(name))
+            .collect();
+        if !asked.is_empty() {
+            prompt
+                .push_str("\n\nAfter the filename, add one line for each of these, in this form:");
+            for (name, description) in &asked {
+                prompt.push_str(&format!("\n{}: <{}>", name, description));
+            }
+        }
+        let messages = self.messages(image_path, &prompt, false)?;
+        let answers = self
+            .make_ai_request(&messages, false, count)
+            .await
+            .map_err(RequestError::into_inner)?;
+        Ok(answers
+            .iter()
+            .map(|answer| parse_suggestion(answer, fields))
+            .collect())
+    }
+
+    // the system prompt, one user/assistant turn per example, then the
+    // image being named
+    fn messages(
+        &self,
+        image_path: &PathBuf,
+        prompt: &str,
+        structured: bool,
+    ) -> Result<Vec<serde_json::Value>, anyhow::Error> {
         // Read the image file and base64-encode it
-        let mut file = File::open(image_path).expect("Failed to open image file");
+        let mut file = File::open(image_path)
+            .map_err(|e| anyhow::anyhow!("Failed to open image file: {}", e))?;
         let mut buffer = Vec::new();
         file.read_to_end(&mut buffer)
-            .expect("Failed to read image file");
+            .map_err(|e| anyhow::anyhow!("Failed to read image file: {}", e))?;
         let encoded_image = base64::encode(&buffer);
 
-        // Create the JSON payload
-        let payload = json!({
-            "model": self.model,
-            "messages": [
-                    {
-                        "role": "system",
-                        "content": r#"You are a filename generation bot. You must return only a filename based on the attached image. No explanations.
-                         No descriptions. No punctuation. No quotes. No code blocks. Just a lowercase hyphenated filename of 3 to 8 words in plain text."#
-                    },
-                    {
-                        "role": "user",
-                        "content": [
-                    {
-                        "type": "text",
-                        "text": self.prompt
-                    },
+        let mut system_prompt = self.system_prompt.clone();
+        if let Some(language) = &self.language {
+            system_prompt.push_str(&format!(
+                " Write the filename in {}, in its own script.",
+                prompt::language_name(language)
+            ));
+        }
+        if structured {
+            system_prompt.push(' ');
+            system_prompt.push_str(STRUCTURED_INSTRUCTIONS);
+        }
+        let mut messages = vec![json!({
+            "role": "system",
+            "content": system_prompt
+        })];
+        for example in &self.examples {
+            messages.push(json!({
+                "role": "user",
+                "content": [
                     {
                         "type": "image_url",
                         "image_url": {
-                            "url": format!("data:image/png;base64,{}", encoded_image),
+                            "url": example.image_url,
                             "detail": "low"
                         }
                     }
                 ]
+            }));
+            messages.push(json!({
+                "role": "assistant",
+                "content": example.name
+            }));
+        }
+        messages.push(json!({
+            "role": "user",
+            "content": [
+                {
+                    "type": "text",
+                    "text": prompt
+                },
+                {
+                    "type": "image_url",
+                    "image_url": {
+                        "url": format!("data:image/png;base64,{}", encoded_image),
+                        "detail": "low"
+                    }
+                }
+            ]
+        }));
+        Ok(messages)
+    }
+
+    // the text of count answers. servers that ignore `n` are asked again
+    // until there are enough.
+    async fn make_ai_request(
+        &self,
+        messages: &[serde_json::Value],
+        structured: bool,
+        count: usize,
+    ) -> Result<Vec<String>, RequestError> {
+        let mut answers = vec![];
+        while answers.len() < count {
+            let choices = self
+                .request_choices(messages, structured, count - answers.len())
+                .await?;
+            if choices.is_empty() {
+                break;
             }
-            ],
+            answers.extend(choices);
+        }
+        if answers.is_empty() {
+            answers.push(UNKNOWN_NAME.to_string());
+        }
+        answers.truncate(count.max(1));
+        Ok(answers)
+    }
+
+    async fn request_choices(
+        &self,
+        messages: &[serde_json::Value],
+        structured: bool,
+        count: usize,
+    ) -> Result<Vec<String>, RequestError> {
+        // Create the JSON payload
+        let mut payload = json!({
+            "model": self.model,
+            "messages": messages,
         });
+        if count > 1 {
+            payload["n"] = json!(count);
+        }
+        if structured {
+            payload["response_format"] = json!({
+                "type": "json_schema",
+                "json_schema": NameResponse::schema()
+            });
+        }
 
-        // Send the request to OpenAI API
-        self.make_ai_request(&payload).await
-    }
+        let estimated = estimate_tokens(messages, count);
+        if let Some(limit) = self.rate_limit {
+            ratelimit::acquire(&self.base_url, limit, estimated).await;
+        }
 
-    async fn make_ai_request(&self, payload: &serde_json::Value) -> String {
         let response = reqwest::Client::new()
-            .post("https://api.openai.com/v1/chat/completions")
-            .header(AUTHORIZATION, format!("Bearer {}", self.api_key))
+            .post(format!("{}/chat/completions", self.base_url))
+            .header(AUTHORIZATION, format!("Bearer {}", self.api_key.expose()))
             .header(CONTENT_TYPE, "application/json")
             .body(payload.to_string())
             .send()
             .await
-            .expect("Failed to send request");
+            .map_err(|e| RequestError::Other(anyhow::anyhow!("Failed to send request: {}", e)))?;
+
+        let status = response.status();
+        let response_text = response.text().await.map_err(|e| {
+            RequestError::Other(anyhow::anyhow!("Failed to get response text: {}", e))
+        })?;
+        if !status.is_success() {
+            let error = anyhow::anyhow!("API returned {}: {}", status, response_text.trim());
+            // servers without structured output reject the unknown field
+            let rejected = status.is_client_error()
+                && ["response_format", "json_schema"]
+                    .iter()
+                    .any(|field| response_text.contains(field));
+            return Err(if structured && rejected {
+                RequestError::Unsupported(error)
+            } else {
+                RequestError::Other(error)
+            });
+        }
 
         // Parse and extract the filename
-        let response_text = response.text().await.expect("Failed to get response text");
-        let response_json: serde_json::Value =
-            serde_json::from_str(&response_text).expect("Failed to parse response");
+        let response_json: serde_json::Value = serde_json::from_str(&response_text)
+            .map_err(|e| RequestError::Other(anyhow::anyhow!("Failed to parse response: {}", e)))?;
+
+        let used = self.record_usage(&response_json["usage"]);
+        if let (Some(limit), Some(used)) = (self.rate_limit, used) {
+            ratelimit::settle(&self.base_url, limit, estimated, used).await;
+        }
+
+        let names = response_json["choices"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|choice| choice["message"]["content"].as_str())
+            .map(|name| name.trim().to_string())
+            .collect();
+
+        Ok(names)
+    }
+
+    // records the tokens a request used and returns their total, None when
+    // the server does not report them
+    fn record_usage(&self, usage: &serde_json::Value) -> Option<u64> {
+        let tokens = |key: &str| usage[key].as_u64().unwrap_or(0);
+        let (prompt_tokens, completion_tokens) =
+            (tokens("prompt_tokens"), tokens("completion_tokens"));
+        if prompt_tokens == 0 && completion_tokens == 0 {
+            return None;
+        }
+        usage::record(&usage::Record {
+            time: chrono::Local::now(),
+            model: self.model.clone(),
+            profile: self.profile.clone(),
+            prompt_tokens,
+            completion_tokens,
+            cost_usd: budget::price_for(&self.prices, &self.model)
+                .map(|price| price.cost(prompt_tokens, completion_tokens)),
+        });
+        Some(prompt_tokens + completion_tokens)
+    }
+}
 
-        let name = response_json["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("unknown-name")
-            .trim()
-            .to_string();
+enum RequestError {
+    // the server does not take `response_format`
+    Unsupported(anyhow::Error),
+    Other(anyhow::Error),
+}
+
+impl RequestError {
+    fn into_inner(self) -> anyhow::Error {
+        match self {
+            RequestError::Unsupported(e) | RequestError::Other(e) => e,
+        }
+    }
+}
+
+// a rough count of the tokens a request uses before the server reports
+// them: four characters of text per token, 85 per low detail image and
+// room for each answer
+fn estimate_tokens(messages: &[serde_json::Value], count: usize) -> u64 {
+    let mut tokens = 0;
+    for message in messages {
+        let parts = match &message["content"] {
+            serde_json::Value::Array(parts) => parts.clone(),
+            content => vec![json!({ "type": "text", "text": content })],
+        };
+        for part in parts {
+            tokens += match part["type"].as_str() {
+                Some("image_url") => 85,
+                _ => part["text"]
+                    .as_str()
+                    .map_or(0, |text| text.len() as u64 / 4),
+            };
+        }
+    }
+    tokens + 100 * count.max(1) as u64
+}
 
-        name
+// the first line is the name, `field: value` lines after it answer the
+// fields that were asked for
+fn parse_suggestion(answer: &str, fields: &[&str]) -> Suggestion {
+    let mut suggestion = Suggestion::default();
+    for line in answer.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let field = line
+            .split_once(':')
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim()))
+            .filter(|(name, _)| fields.contains(&name.as_str()));
+        match field {
+            Some((name, value)) => {
+                let value = value.split_whitespace().collect::<Vec<_>>().join("-");
+                suggestion.fields.insert(name, value);
+            }
+            None if suggestion.name.is_empty() => suggestion.name = line.to_string(),
+            None => {}
+        }
+    }
+    if suggestion.name.is_empty() {
+        suggestion.name = UNKNOWN_NAME.to_string();
     }
+    suggestion
 }
@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use chrono::{DateTime, Local};
+use log::error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{config::setup::state_dir, manager::naming::Suggestion, utils::lock::FileLock};
+
+pub const DEFAULT_TTL_DAYS: u64 = 30;
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+// how many answers were served from the cache, a single number kept apart
+// from the entries so a lookup never rewrites one
+const HITS_FILE: &str = "hits";
+
+// the answers for one image and request, in cache/<key>.json in the state
+// dir. each entry has a file of its own, so the daemon and the cli never
+// rewrite each other's entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    time: DateTime<Local>,
+    suggestions: Vec<Suggestion>,
+}
+
+fn get_cache_dir() -> Result<PathBuf, anyhow::Error> {
+    let dir = state_dir()?.join("cache");
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn is_fresh(entry: &Entry, ttl: Duration) -> bool {
+    chrono::Duration::from_std(ttl).is_ok_and(|ttl| Local::now() - entry.time < ttl)
+}
+
+// the key for an image's bytes sent to model at provider with a prompt.
+// the prompt covers everything else that changes the answer, such as the
+// system prompt and examples.
+pub fn key(image: &[u8], provider: &str, model: &str, prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [
+        format!("{:x}", Sha256::digest(image)),
+        provider.to_string(),
+        model.to_string(),
+        format!("{:x}", Sha256::digest(prompt.as_bytes())),
+    ] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+// the answers stored for key, unless they are older than ttl
+pub async fn get(key: &str, ttl: Duration) -> Option<Vec<Suggestion>> {
+    let key = key.to_string();
+    let result = tokio::task::spawn_blocking(move || lookup(&key, ttl))
+        .await
+        .unwrap_or_else(|e| Err(e.into()));
+    match result {
+        Ok(suggestions) => suggestions,
+        Err(e) => {
+            error!("Failed to read the cache: {}", e);
+            None
+        }
+    }
+}
+
+fn lookup(key: &str, ttl: Duration) -> Result<Option<Vec<Suggestion>>, anyhow::Error> {
+    let dir = get_cache_dir()?;
+    let raw = match fs::read_to_string(dir.join(format!("{}.json", key))) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    // an unreadable entry is only a miss, the next answer replaces it
+    let entry = match serde_json::from_str::<Entry>(&raw) {
+        Ok(entry) if is_fresh(&entry, ttl) => entry,
+        _ => return Ok(None),
+    };
+    // a lost count is no reason to ask the model again
+    count_hit(&dir).ok();
+    Ok(Some(entry.suggestions))
+}
+
+fn count_hit(dir: &Path) -> Result<(), anyhow::Error> {
+    let lock = FileLock::acquire(&dir.join(HITS_FILE))?;
+    let mut file = lock.file();
+    let mut raw = String::new();
+    file.read_to_string(&mut raw)?;
+    let hits = raw.trim().parse::<u64>().unwrap_or(0) + 1;
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(hits.to_string().as_bytes())?;
+    Ok(())
+}
+
+// stores the answers for key, dropping expired entries and then the oldest
+// ones past max_entries
+pub async fn put(key: &str, suggestions: &[Suggestion], ttl: Duration, max_entries: usize) {
+    let (key, suggestions) = (key.to_string(), suggestions.to_vec());
+    let result = tokio::task::spawn_blocking(move || store(&key, suggestions, ttl, max_entries))
+        .await
+        .unwrap_or_else(|e| Err(e.into()));
+    if let Err(e) = result {
+        error!("Failed to update the cache: {}", e);
+    }
+}
+
+fn store(
+    key: &str,
+    suggestions: Vec<Suggestion>,
+    ttl: Duration,
+    max_entries: usize,
+) -> Result<(), anyhow::Error> {
+    let dir = get_cache_dir()?;
+    let entry = Entry {
+        time: Local::now(),
+        suggestions,
+    };
+    // written to a temporary file first so a crash never leaves half an
+    // entry, named after the process so two of them never share it
+    let path = dir.join(format!("{}.json", key));
+    let temp = dir.join(format!("{}.{}.tmp", key, std::process::id()));
+    fs::write(&temp, serde_json::to_string(&entry)?)?;
+    fs::rename(&temp, &path)?;
+
+    let mut entries = list(&dir)?;
+    entries.retain(|(path, written)| {
+        let fresh = is_recent(*written, ttl);
+        if !fresh {
+            fs::remove_file(path).ok();
+        }
+        fresh
+    });
+    if entries.len() > max_entries {
+        entries.sort_by_key(|(_, written)| *written);
+        for (path, _) in &entries[..entries.len() - max_entries] {
+            fs::remove_file(path).ok();
+        }
+    }
+    Ok(())
+}
+
+// every entry file with when it was written, which is the entry's time
+fn list(dir: &Path) -> Result<Vec<(PathBuf, SystemTime)>, anyhow::Error> {
+    let mut entries = vec![];
+    for file in fs::read_dir(dir)? {
+        let path = file?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        // removed by another process in the meantime
+        if let Ok(written) = fs::metadata(&path).and_then(|m| m.modified()) {
+            entries.push((path, written));
+        }
+    }
+    Ok(entries)
+}
+
+fn is_recent(written: SystemTime, ttl: Duration) -> bool {
+    SystemTime::now()
+        .duration_since(written)
+        .map_or(true, |age| age < ttl)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Stats {
+    pub entries: usize,
+    // past the ttl, dropped on the next write
+    pub expired: usize,
+    pub hits: u64,
+    pub size_bytes: u64,
+    pub oldest: Option<DateTime<Local>>,
+    pub newest: Option<DateTime<Local>>,
+}
+
+pub fn stats(ttl: Duration) -> Stats {
+    let dir = match get_cache_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            error!("Failed to read the cache: {}", e);
+            return Stats::default();
+        }
+    };
+    let entries = list(&dir).unwrap_or_default();
+    let size = |path: &Path| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let times = entries
+        .iter()
+        .map(|(_, written)| DateTime::<Local>::from(*written));
+    Stats {
+        entries: entries.len(),
+        expired: entries
+            .iter()
+            .filter(|(_, written)| !is_recent(*written, ttl))
+            .count(),
+        hits: fs::read_to_string(dir.join(HITS_FILE))
+            .ok()
+            .and_then(|hits| hits.trim().parse().ok())
+            .unwrap_or(0),
+        size_bytes: entries.iter().map(|(path, _)| size(path)).sum(),
+        oldest: times.clone().min(),
+        newest: times.max(),
+    }
+}
+
+// removes every entry, returning how many there were
+pub fn clear() -> Result<usize, anyhow::Error> {
+    let dir = get_cache_dir()?;
+    let count = list(&dir)?.len();
+    fs::remove_dir_all(&dir)?;
+    // the single file cache of earlier versions
+    let state = state_dir()?;
+    fs::remove_file(state.join("cache.json")).ok();
+    fs::remove_file(state.join("cache.lock")).ok();
+    Ok(count)
+}
@@ -0,0 +1,82 @@
+#![allow(deprecated)]
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::ImageFormat;
+
+pub const DEFAULT_MAX_EXAMPLES: usize = 5;
+
+// examples only show the naming style, so they are sent small to keep the
+// request cheap
+const MAX_EXAMPLE_SIDE: u32 = 512;
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "gif"];
+
+// an image and the name it should get, sent as a few-shot turn before the
+// image being named
+#[derive(Debug, Clone)]
+pub struct Example {
+    pub name: String,
+    pub image_url: String,
+}
+
+// images in dir, named the way they should be named. a `<stem>.txt` next to
+// an image holds its expected name instead of the file name.
+fn example_files(dir: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        anyhow::anyhow!("Failed to read examples directory {}: {}", dir.display(), e)
+    })?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+fn expected_name(image: &Path) -> String {
+    let stem = image
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    fs::read_to_string(image.with_extension("txt"))
+        .ok()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(stem)
+}
+
+// the image scaled down to fit MAX_EXAMPLE_SIDE, as a JPEG data url
+fn downscaled(image: &Path) -> Result<String, anyhow::Error> {
+    let decoded = image::open(image)
+        .map_err(|e| anyhow::anyhow!("Failed to read example {}: {}", image.display(), e))?;
+    let small = decoded
+        .thumbnail(MAX_EXAMPLE_SIDE, MAX_EXAMPLE_SIDE)
+        .to_rgb8();
+
+    let mut buffer = Cursor::new(Vec::new());
+    small.write_to(&mut buffer, ImageFormat::Jpeg)?;
+    let encoded = base64::encode(buffer.into_inner());
+    Ok(format!("data:image/jpeg;base64,{}", encoded))
+}
+
+// the first max examples in dir, by file name
+pub fn load(dir: &Path, max: usize) -> Result<Vec<Example>, anyhow::Error> {
+    example_files(dir)?
+        .into_iter()
+        .take(max)
+        .map(|image| {
+            Ok(Example {
+                name: expected_name(&image),
+                image_url: downscaled(&image)?,
+            })
+        })
+        .collect()
+}
@@ -1,28 +1,201 @@
+use log::{info, warn};
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
-use crate::manager::ai::OpenAI;
+use crate::{
+    config::{budget::Budget, config::Config, profile::expand_dir},
+    manager::{
+        ai::OpenAI,
+        naming::{self, Suggestion},
+        score::{self, Candidate},
+        style::{self, NamingStyle},
+        usage::{self, BudgetExceeded, BudgetState},
+    },
+};
+
+// used when neither the config nor the profile sets a template
+pub const DEFAULT_NAMING_TEMPLATE: &str = "{ai}";
 
 #[derive(Debug, Clone)]
 pub struct SSManager {
     ai: OpenAI,
+    naming_template: String,
+    naming_style: Option<NamingStyle>,
+    transliterate: bool,
+    // names asked for per image, the best one is used
+    candidates: usize,
+    banned_words: Vec<String>,
+    // renamed files are moved here, or stay next to the original when unset
+    destination: Option<PathBuf>,
+    budget: Budget,
+    // the budget's fallback profile, asked once a cap is reached
+    fallback: Option<OpenAI>,
 }
 
 impl SSManager {
     pub fn new(ai: OpenAI) -> Self {
-        Self { ai }
+        Self {
+            ai,
+            naming_template: DEFAULT_NAMING_TEMPLATE.to_string(),
+            naming_style: None,
+            transliterate: false,
+            candidates: 1,
+            banned_words: score::DEFAULT_BANNED_WORDS
+                .iter()
+                .map(|word| word.to_string())
+                .collect(),
+            destination: None,
+            budget: Budget::default(),
+            fallback: None,
+        }
+    }
+
+    // a manager for the config with its profile already applied
+    pub fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
+        let ai = OpenAI::from_config(config)?;
+        let naming_template = config
+            .naming_template
+            .clone()
+            .filter(|template| !template.is_empty())
+            .unwrap_or_else(|| DEFAULT_NAMING_TEMPLATE.to_string());
+        naming::check(&naming_template)
+            .map_err(|e| anyhow::anyhow!("Invalid naming template {:?}: {}", naming_template, e))?;
+        let budget = config.budget();
+        if let Some(model) = ai.unpriced_model().filter(|_| budget.has_caps()) {
+            warn!(
+                "[Budget] No price for model {:?}, its requests do not count against the budget. Add it to `prices`",
+                model
+            );
+        }
+        let fallback =
+            match budget.fallback_profile.as_deref() {
+                Some(name) if config.profile.as_deref() != Some(name) => {
+                    let fallback = config.with_profile(Some(name))?;
+                    Some(OpenAI::from_config(&fallback).map_err(|e| {
+                        anyhow::anyhow!("Invalid fallback profile {:?}: {}", name, e)
+                    })?)
+                }
+                _ => None,
+            };
+        Ok(Self {
+            ai,
+            naming_template,
+            naming_style: config.naming_style,
+            transliterate: config.transliterate.unwrap_or(false),
+            candidates: config.candidates(),
+            banned_words: config.banned_words(),
+            destination: config
+                .destination
+                .as_deref()
+                .filter(|dir| !dir.is_empty())
+                .map(expand_dir),
+            budget,
+            fallback,
+        })
+    }
+
+    // the provider within budget. errors with `BudgetExceeded` when a hard
+    // cap is reached and there is no fallback.
+    fn provider(&self) -> Result<&OpenAI, anyhow::Error> {
+        match usage::budget_state(&self.budget) {
+            BudgetState::Within => Ok(&self.ai),
+            BudgetState::Soft(reason) => match &self.fallback {
+                Some(fallback) => {
+                    warn!("[Budget] {}, using the fallback profile", reason);
+                    Ok(fallback)
+                }
+                None => {
+                    warn!("[Budget] {}", reason);
+                    Ok(&self.ai)
+                }
+            },
+            BudgetState::Hard(reason) => match &self.fallback {
+                Some(fallback) => {
+                    warn!("[Budget] {}, using the fallback profile", reason);
+                    Ok(fallback)
+                }
+                None => Err(BudgetExceeded(reason).into()),
+            },
+        }
+    }
+
+    // the new file name for path, extension included
+    pub async fn suggest_name(&self, path: &PathBuf) -> Result<String, anyhow::Error> {
+        Ok(self.suggest(path).await?.0)
+    }
+
+    // the best new file name along with everything the model said about
+    // the image
+    pub async fn suggest(&self, path: &PathBuf) -> Result<(String, Suggestion), anyhow::Error> {
+        let best = self.candidates(path).await?.remove(0);
+        Ok((best.file_name, best.suggestion))
+    }
+
+    // the configured number of names for path, best first. never empty.
+    pub async fn candidates(&self, path: &PathBuf) -> Result<Vec<Candidate>, anyhow::Error> {
+        let extension = match path.extension() {
+            Some(ext) => ext.to_string_lossy().to_string(),
+            None => return Err(anyhow::anyhow!("Failed to get file extension")),
+        };
+        // only ask the model for the fields the template uses
+        let fields: Vec<&str> = naming::AI_FIELDS
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| naming::uses(&self.naming_template, name))
+            .collect();
+        let dir = self.target_dir(path);
+
+        let mut candidates = vec![];
+        let ai = self.provider()?;
+        for mut suggestion in ai.get_names(path, &fields, self.candidates).await? {
+            suggestion.name = style::apply(&suggestion.name, self.naming_style, self.transliterate);
+            for value in suggestion.fields.values_mut() {
+                *value = style::apply(value, self.naming_style, self.transliterate);
+            }
+            let name = naming::render(&self.naming_template, path, &suggestion, &dir, &extension)?;
+            // the image keeping its own name is not a clash
+            let target = dir.join(&name);
+            let taken = target.exists() && !is_same_file(&target, path);
+            let mut candidate = Candidate::new(name, suggestion, taken, &self.banned_words);
+            if taken {
+                candidate.file_name = free_name(&dir, &candidate.file_name, path);
+            }
+            candidates.push(candidate);
+        }
+        if candidates.is_empty() {
+            return Err(anyhow::anyhow!("The model suggested no name"));
+        }
+        Ok(score::rank(candidates))
+    }
+
+    // the directory a renamed file goes to
+    fn target_dir(&self, path: &Path) -> PathBuf {
+        match &self.destination {
+            Some(dir) => dir.clone(),
+            None => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        }
     }
 
-    fn modify_ss_path(&self, path: &PathBuf) -> PathBuf {
+    // where a renamed file goes, creating the destination if needed
+    fn target_path(&self, path: &Path, file_name: &str) -> Result<PathBuf, anyhow::Error> {
+        let dir = self.target_dir(path);
+        fs::create_dir_all(&dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create destination {:?}: {}", dir, e))?;
+        Ok(dir.join(file_name))
+    }
+
+    pub fn modify_ss_path(&self, path: &Path) -> PathBuf {
         // initially the path of the file starts with .<file_name>
         // we need to remove the . from the file name
-        let filename = path.file_name().unwrap().to_str().unwrap()[1..].to_string();
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        let filename = filename.strip_prefix('.').unwrap_or(filename);
         let parent = path.parent().unwrap_or(Path::new("."));
         parent.join(filename)
     }
 
-    pub fn is_screenshot_file(&self, path: &PathBuf) -> bool {
+    pub fn is_screenshot_file(&self, path: &Path) -> bool {
         if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
             let mut lowercase = filename.to_lowercase();
             if lowercase.starts_with(".") {
@@ -30,55 +203,65 @@ impl SSManager {
             }
             return (lowercase.starts_with("screenshot") || lowercase.contains("screen shot"))
                 && !lowercase.ends_with("-ss")
-                && path.extension().map_or(false, |ext| ext == "png");
+                && path.extension().is_some_and(|ext| ext == "png");
         }
         false
     }
 
-    fn is_recent(&self, path: &PathBuf, max_age: Duration) -> bool {
-        if let Ok(metadata) = fs::metadata(path) {
-            if let Ok(created) = metadata.created().or_else(|_| metadata.modified()) {
-                return SystemTime::now()
-                    .duration_since(created)
-                    .unwrap_or(Duration::MAX)
-                    < max_age;
-            }
+    fn created_at(&self, path: &Path) -> Option<SystemTime> {
+        let metadata = fs::metadata(path).ok()?;
+        metadata.created().or_else(|_| metadata.modified()).ok()
+    }
+
+    fn is_recent(&self, path: &Path, max_age: Duration) -> bool {
+        if let Some(created) = self.created_at(path) {
+            return SystemTime::now()
+                .duration_since(created)
+                .unwrap_or(Duration::MAX)
+                < max_age;
         }
         false
     }
 
-    fn delete_file(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
-        if let Err(e) = fs::remove_file(path) {
-            return Err(anyhow::anyhow!(
-                "Failed to delete file: {:?}, Error: {}",
-                path,
-                e
-            ));
-        }
-        Ok(())
+    // lists screenshots in dir created within (since, until], oldest first.
+    // hidden files are skipped as they are still being written by the OS.
+    pub fn find_missed_screenshots(
+        &self,
+        dir: &Path,
+        since: SystemTime,
+        until: SystemTime,
+    ) -> Vec<PathBuf> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return vec![],
+        };
+
+        let mut missed: Vec<(SystemTime, PathBuf)> = entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                let hidden = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with('.'));
+                !hidden && self.is_screenshot_file(path)
+            })
+            .filter_map(|path| self.created_at(&path).map(|created| (created, path)))
+            .filter(|(created, _)| *created > since && *created <= until)
+            .collect();
+
+        missed.sort();
+        missed.into_iter().map(|(_, path)| path).collect()
     }
 
     async fn process_ss(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
-        // create new filename
-        let mut new_filename = self.ai.get_name(&path).await;
-        new_filename += ".png";
-
         // create new path
-        let parent = path.parent().unwrap_or(Path::new("."));
-        let new_path = parent.join(new_filename);
-
-        // copy file to new path
-        if let Err(e) = fs::copy(path.clone(), &new_path) {
-            return Err(anyhow::anyhow!(
-                "Failed to copy file: {:?} -> {:?}, Error: {}",
-                path.clone(),
-                new_path,
-                e
-            ));
+        let new_filename = self.suggest_name(path).await?;
+        let new_path = self.target_path(path, &new_filename)?;
+        if is_same_file(&new_path, path) {
+            info!("{:?} already has its name", path);
+            return Ok(());
         }
-
-        // delete old file
-        self.delete_file(&path)
+        move_file(path, &new_path)
     }
 
     pub async fn process_new_ss(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
@@ -98,32 +281,83 @@ impl SSManager {
         self.process_ss(&path).await
     }
 
-    pub async fn process_random_image(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
-        let file_type = match path.extension() {
-            Some(ext) => ext.to_str().unwrap(),
-            None => return Err(anyhow::anyhow!("Failed to get file extension")),
-        };
+    // screenshots found by the startup catch-up scan already carry their final
+    // name, so unlike `process_new_ss` there is no path fix-up or age check.
+    pub async fn process_missed_ss(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
+        if !self.is_screenshot_file(path) {
+            return Err(anyhow::anyhow!("file is not screenshot: {:?}", path));
+        }
 
-        let parent = path.parent().unwrap_or(Path::new("."));
+        self.process_ss(path).await
+    }
 
+    pub async fn process_random_image(&self, path: &PathBuf) -> Result<(), anyhow::Error> {
         println!("Processing image: {:?}", path);
-        let mut new_filename: String = self.ai.get_name(&path).await;
-        new_filename += &format!(".{}", file_type);
+        let new_filename = self.suggest_name(path).await?;
+        self.rename_to(path, &new_filename)
+    }
 
-        let new_path = parent.join(new_filename);
+    // moves path to file_name in the destination, e.g. a name picked from
+    // the candidates
+    pub fn rename_to(&self, path: &PathBuf, new_filename: &str) -> Result<(), anyhow::Error> {
+        let new_path = self.target_path(path, new_filename)?;
+        if is_same_file(&new_path, path) {
+            println!("{:?} already has this name", path);
+            return Ok(());
+        }
 
         println!("New filename: {:?}", new_path);
+        move_file(path, &new_path)
+    }
+}
 
-        if let Err(e) = fs::copy(path.clone(), &new_path) {
-            return Err(anyhow::anyhow!(
-                "Failed to copy file: {:?} -> {:?}, Error: {}",
-                path,
-                new_path,
-                e
-            ));
-        }
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+// file_name, numbered from 2 until no file in dir but source has it
+fn free_name(dir: &Path, file_name: &str, source: &Path) -> String {
+    (2..)
+        .map(|n| naming::numbered(file_name, n))
+        .find(|name| {
+            let target = dir.join(name);
+            !target.exists() || is_same_file(&target, source)
+        })
+        .unwrap_or_else(|| file_name.to_string())
+}
 
-        self.delete_file(&path)
-            .map_err(|e| anyhow::anyhow!("Failed to delete file: {:?}, Error: {}", path, e))
+// moves from to to, failing rather than replacing a file that is already
+// there. a hard link does it atomically, a new file is copied into when to
+// is on another volume.
+fn move_file(from: &Path, to: &Path) -> Result<(), anyhow::Error> {
+    let moved = fs::hard_link(from, to).or_else(|e| {
+        if e.kind() == io::ErrorKind::AlreadyExists {
+            return Err(e);
+        }
+        let mut source = fs::File::open(from)?;
+        let mut target = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(to)?;
+        let copied = io::copy(&mut source, &mut target)
+            .and_then(|_| target.set_permissions(source.metadata()?.permissions()));
+        if copied.is_err() {
+            fs::remove_file(to).ok();
+        }
+        copied
+    });
+    if let Err(e) = moved {
+        return Err(anyhow::anyhow!(
+            "Failed to move file: {:?} -> {:?}, Error: {}",
+            from,
+            to,
+            e
+        ));
     }
+
+    fs::remove_file(from)
+        .map_err(|e| anyhow::anyhow!("Failed to delete file: {:?}, Error: {}", from, e))
 }
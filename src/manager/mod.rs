@@ -1,2 +1,11 @@
 pub mod ai;
+pub mod cache;
+pub mod examples;
 pub mod image;
+pub mod naming;
+pub mod prompt;
+pub mod ratelimit;
+pub mod sanitize;
+pub mod score;
+pub mod style;
+pub mod usage;
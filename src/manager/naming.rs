@@ -0,0 +1,301 @@
+use std::{collections::BTreeMap, fs, io::BufReader, path::Path, time::SystemTime};
+
+use chrono::{format::StrftimeItems, DateTime, Local, NaiveDate, TimeZone};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::manager::sanitize;
+
+// fields a naming template can use as `{name}` or `{name:spec}`
+pub const FIELDS: &[&str] = &[
+    "ai", "app", "category", "date", "time", "stem", "hash", "width", "height", "seq",
+];
+
+// fields the model is asked for next to the name, with how they are
+// described to it
+pub const AI_FIELDS: &[(&str, &str)] = &[
+    ("app", "the application shown, in one or two words"),
+    ("category", "a one or two word category for the image"),
+];
+
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+// colons are not allowed in file names on macOS and Windows
+const DEFAULT_TIME_FORMAT: &str = "%H-%M-%S";
+const DEFAULT_HASH_LENGTH: usize = 8;
+
+enum Part<'a> {
+    Text(&'a str),
+    Field {
+        name: &'a str,
+        spec: Option<&'a str>,
+    },
+}
+
+fn parse(template: &str) -> Result<Vec<Part<'_>>, anyhow::Error> {
+    let mut parts = vec![];
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        parts.push(Part::Text(&rest[..start]));
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("`{{` is never closed with `}}`"))?;
+
+        let (name, spec) = match after[..end].split_once(':') {
+            Some((name, spec)) => (name.trim(), Some(spec)),
+            None => (after[..end].trim(), None),
+        };
+        check_field(name, spec)?;
+        parts.push(Part::Field { name, spec });
+        rest = &after[end + 1..];
+    }
+    parts.push(Part::Text(rest));
+    Ok(parts)
+}
+
+fn check_field(name: &str, spec: Option<&str>) -> Result<(), anyhow::Error> {
+    if !FIELDS.contains(&name) {
+        return Err(anyhow::anyhow!(
+            "unknown field `{{{}}}`, available: {}",
+            name,
+            FIELDS.join(", ")
+        ));
+    }
+    let Some(spec) = spec else {
+        return Ok(());
+    };
+    match name {
+        "date" | "time" => {
+            if StrftimeItems::new(spec).any(|item| item == chrono::format::Item::Error) {
+                return Err(anyhow::anyhow!("`{}` is not a valid date format", spec));
+            }
+        }
+        "seq" => {
+            if spec.is_empty() || !spec.chars().all(|c| c.is_ascii_digit()) {
+                return Err(anyhow::anyhow!(
+                    "`{{seq:{}}}` needs a width such as `{{seq:03}}`",
+                    spec
+                ));
+            }
+        }
+        "hash" => {
+            if !matches!(spec.parse::<usize>(), Ok(1..=64)) {
+                return Err(anyhow::anyhow!(
+                    "`{{hash:{}}}` needs a length from 1 to 64",
+                    spec
+                ));
+            }
+        }
+        _ => {
+            return Err(anyhow::anyhow!("`{{{}}}` takes no format", name));
+        }
+    }
+    Ok(())
+}
+
+// checks the fields of a template without an image
+pub fn check(template: &str) -> Result<(), anyhow::Error> {
+    parse(template).map(|_| ())
+}
+
+pub fn uses(template: &str, field: &str) -> bool {
+    parse(template).is_ok_and(|parts| {
+        parts
+            .iter()
+            .any(|part| matches!(part, Part::Field { name, .. } if *name == field))
+    })
+}
+
+// when the image was taken: the EXIF capture time when there is one, the
+// file's creation time otherwise
+pub fn captured_at(image: &Path) -> DateTime<Local> {
+    exif_captured_at(image).unwrap_or_else(|| {
+        let captured = fs::metadata(image)
+            .and_then(|metadata| metadata.created().or_else(|_| metadata.modified()))
+            .unwrap_or_else(|_| SystemTime::now());
+        DateTime::<Local>::from(captured)
+    })
+}
+
+fn exif_captured_at(image: &Path) -> Option<DateTime<Local>> {
+    let file = fs::File::open(image).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .ok()?;
+    let field = exif
+        .get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+        .or_else(|| exif.get_field(exif::Tag::DateTime, exif::In::PRIMARY))?;
+    let exif::Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let taken = exif::DateTime::from_ascii(values.first()?).ok()?;
+    let naive = NaiveDate::from_ymd_opt(taken.year.into(), taken.month.into(), taken.day.into())?
+        .and_hms_opt(taken.hour.into(), taken.minute.into(), taken.second.into())?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+// what the model answered for an image
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Suggestion {
+    pub name: String,
+    // answers for AI_FIELDS, by field name
+    #[serde(flatten)]
+    pub fields: BTreeMap<String, String>,
+    // only filled in by servers with structured output
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    pub contains_sensitive: bool,
+}
+
+fn hash(image: &Path, length: usize) -> Result<String, anyhow::Error> {
+    let contents = fs::read(image)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", image.display(), e))?;
+    let digest = format!("{:x}", Sha256::digest(&contents));
+    Ok(digest[..length].to_string())
+}
+
+fn value(
+    name: &str,
+    spec: Option<&str>,
+    image: &Path,
+    suggestion: &Suggestion,
+) -> Result<String, anyhow::Error> {
+    let value = match name {
+        "ai" => suggestion.name.clone(),
+        "app" | "category" => suggestion
+            .fields
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| "unknown".to_string()),
+        "date" => captured_at(image)
+            .format(spec.unwrap_or(DEFAULT_DATE_FORMAT))
+            .to_string(),
+        "time" => captured_at(image)
+            .format(spec.unwrap_or(DEFAULT_TIME_FORMAT))
+            .to_string(),
+        "stem" => image
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        "hash" => hash(
+            image,
+            spec.and_then(|s| s.parse().ok())
+                .unwrap_or(DEFAULT_HASH_LENGTH),
+        )?,
+        "width" | "height" => {
+            let size = imagesize::size(image).map_err(|e| {
+                anyhow::anyhow!("cannot read the size of {}: {}", image.display(), e)
+            })?;
+            if name == "width" {
+                size.width.to_string()
+            } else {
+                size.height.to_string()
+            }
+        }
+        _ => return Err(anyhow::anyhow!("unknown field `{{{}}}`", name)),
+    };
+    Ok(sanitize::component(&value))
+}
+
+// file_name with `-n` before the extension, the stem shortened when the
+// name would get too long
+pub fn numbered(file_name: &str, n: usize) -> String {
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (file_name, None),
+    };
+    let suffix = format!("-{}", n);
+    let extension_len = extension.map_or(0, |e| e.len() + 1);
+    let max_stem = sanitize::MAX_NAME_BYTES.saturating_sub(extension_len + suffix.len());
+    let stem = sanitize::truncate(stem, max_stem);
+    match extension {
+        Some(extension) => format!("{}{}.{}", stem, suffix, extension),
+        None => format!("{}{}", stem, suffix),
+    }
+}
+
+// a rendered part of the name, the sequence number being filled in last
+enum Rendered {
+    Text(String),
+    // answers from the model, shortened when the name gets too long
+    Model(String),
+    Seq { width: usize },
+}
+
+// digits kept free for `{seq}` when the name is shortened
+const SEQ_RESERVE: usize = 4;
+
+// the file name for image in dir, extension included. `{seq}` becomes the
+// lowest number from 1 whose name is not taken in dir yet.
+pub fn render(
+    template: &str,
+    image: &Path,
+    suggestion: &Suggestion,
+    dir: &Path,
+    extension: &str,
+) -> Result<String, anyhow::Error> {
+    let mut rendered = vec![];
+    for part in parse(template)? {
+        rendered.push(match part {
+            Part::Text(text) => Rendered::Text(text.to_string()),
+            Part::Field { name: "seq", spec } => Rendered::Seq {
+                width: spec.and_then(|s| s.parse().ok()).unwrap_or(0),
+            },
+            Part::Field { name, spec } => {
+                let value = value(name, spec, image, suggestion)?;
+                if name == "ai" || AI_FIELDS.iter().any(|(field, _)| *field == name) {
+                    Rendered::Model(value)
+                } else {
+                    Rendered::Text(value)
+                }
+            }
+        });
+    }
+
+    // only the model's answers are shortened, the rest of the template is
+    // what the user asked for
+    let mut fixed = extension.len() + 1;
+    let mut model_parts = 0;
+    for part in &rendered {
+        match part {
+            Rendered::Text(text) => fixed += text.len(),
+            Rendered::Model(_) => model_parts += 1,
+            Rendered::Seq { width } => fixed += (*width).max(SEQ_RESERVE),
+        }
+    }
+    if fixed >= sanitize::MAX_NAME_BYTES {
+        return Err(anyhow::anyhow!(
+            "Naming template gives a name longer than {} bytes",
+            sanitize::MAX_NAME_BYTES
+        ));
+    }
+    let budget = (sanitize::MAX_NAME_BYTES - fixed) / model_parts.max(1);
+    for part in rendered.iter_mut() {
+        if let Rendered::Model(value) = part {
+            *value = sanitize::truncate(value, budget).to_string();
+        }
+    }
+
+    let name_with = |seq: usize| {
+        let stem: String = rendered
+            .iter()
+            .map(|part| match part {
+                Rendered::Text(text) | Rendered::Model(text) => text.clone(),
+                Rendered::Seq { width } => format!("{:0width$}", seq, width = *width),
+            })
+            .collect();
+        format!("{}.{}", sanitize::stem(&stem), extension)
+    };
+
+    if !rendered
+        .iter()
+        .any(|part| matches!(part, Rendered::Seq { .. }))
+    {
+        return Ok(name_with(0));
+    }
+    let seq = (1..)
+        .find(|seq| !dir.join(name_with(*seq)).exists())
+        .unwrap_or(1);
+    Ok(name_with(seq))
+}
@@ -0,0 +1,258 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::manager::naming;
+
+// variables a prompt file can use as `{{name}}`
+pub const VARIABLES: &[&str] = &[
+    "original_name",
+    "date",
+    "time",
+    "dir",
+    "width",
+    "height",
+    "locale",
+    "language",
+    "existing_names_in_dir",
+    "profile",
+];
+
+// the listing of the image's directory is capped so a huge folder does not
+// blow up the prompt
+const MAX_EXISTING_NAMES: usize = 100;
+
+enum Part<'a> {
+    Text(&'a str),
+    Variable { name: &'a str, line: usize },
+}
+
+fn line_of(template: &str, offset: usize) -> usize {
+    template[..offset].matches('\n').count() + 1
+}
+
+// splits template into text and variables. `\{{` is a literal `{{`, and so
+// is any `{{` that does not start a known variable, e.g. in a JSON example,
+// so prompts written before templates were sent as written keep working.
+// such a `{{` around what looks like a misspelled variable gives a warning.
+fn parse(template: &str) -> (Vec<Part<'_>>, Vec<String>) {
+    let mut parts = vec![];
+    let mut warnings = vec![];
+    let mut rest = template;
+    let mut offset = 0;
+
+    while let Some(start) = rest.find("{{") {
+        let line = line_of(template, offset + start);
+        let after = &rest[start + 2..];
+        let consumed = match after.find("}}").map(|end| (end, after[..end].trim())) {
+            _ if rest[..start].ends_with('\\') => {
+                parts.push(Part::Text(&rest[..start - 1]));
+                parts.push(Part::Text("{{"));
+                start + 2
+            }
+            Some((end, name)) if VARIABLES.contains(&name) => {
+                parts.push(Part::Text(&rest[..start]));
+                parts.push(Part::Variable { name, line });
+                start + 2 + end + 2
+            }
+            found => {
+                match found {
+                    Some((_, name)) if is_identifier(name) => warnings.push(format!(
+                        "line {}: unknown variable `{{{{{}}}}}` is sent as written, available: {}",
+                        line,
+                        name,
+                        VARIABLES.join(", ")
+                    )),
+                    None => warnings.push(format!(
+                        "line {}: `{{{{` is never closed with `}}}}` and is sent as written",
+                        line
+                    )),
+                    Some(_) => {}
+                }
+                parts.push(Part::Text(&rest[..start + 2]));
+                start + 2
+            }
+        };
+        offset += consumed;
+        rest = &rest[consumed..];
+    }
+    parts.push(Part::Text(rest));
+    (parts, warnings)
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// what looks wrong in a prompt, checked without an image. nothing here stops
+// a rename.
+pub fn check(template: &str) -> Vec<String> {
+    parse(template).1
+}
+
+// per-image values for the variables, computed only when a prompt uses them
+pub struct PromptContext<'a> {
+    pub image: &'a Path,
+    pub profile: Option<&'a str>,
+    pub locale: Option<&'a str>,
+}
+
+impl PromptContext<'_> {
+    fn dimensions(&self) -> Result<imagesize::ImageSize, anyhow::Error> {
+        imagesize::size(self.image)
+            .map_err(|e| anyhow::anyhow!("cannot read the size of {}: {}", self.image.display(), e))
+    }
+
+    fn dir(&self) -> PathBuf {
+        self.image.parent().unwrap_or(Path::new(".")).to_path_buf()
+    }
+
+    fn existing_names(&self) -> String {
+        let mut names: Vec<String> = fs::read_dir(self.dir())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path != self.image && path.is_file())
+            .filter_map(|path| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+            .filter(|stem| !stem.starts_with('.'))
+            .collect();
+        names.sort();
+        names.truncate(MAX_EXISTING_NAMES);
+        names.join(", ")
+    }
+
+    fn value(&self, name: &str) -> Result<String, anyhow::Error> {
+        Ok(match name {
+            "original_name" => self
+                .image
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            "date" => naming::captured_at(self.image)
+                .format("%Y-%m-%d")
+                .to_string(),
+            "time" => naming::captured_at(self.image)
+                .format("%H:%M:%S")
+                .to_string(),
+            "dir" => self.dir().display().to_string(),
+            "width" => self.dimensions()?.width.to_string(),
+            "height" => self.dimensions()?.height.to_string(),
+            "locale" => self
+                .locale
+                .map(str::to_string)
+                .unwrap_or_else(system_locale),
+            "language" => language_name(
+                &self
+                    .locale
+                    .map(str::to_string)
+                    .unwrap_or_else(system_locale),
+            ),
+            "existing_names_in_dir" => self.existing_names(),
+            "profile" => self.profile.unwrap_or("default").to_string(),
+            _ => return Err(anyhow::anyhow!("unknown variable `{{{{{}}}}}`", name)),
+        })
+    }
+}
+
+// e.g. en_US from LC_ALL, LC_MESSAGES or LANG, without the encoding
+pub fn system_locale() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .map(|value| value.split('.').next().unwrap_or_default().to_string())
+        .unwrap_or_else(|| "en_US".to_string())
+}
+
+// languages the model is most often asked for, by their ISO 639-1 code
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("ar", "Arabic"),
+    ("de", "German"),
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("fr", "French"),
+    ("hi", "Hindi"),
+    ("it", "Italian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("nl", "Dutch"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ru", "Russian"),
+    ("sv", "Swedish"),
+    ("tr", "Turkish"),
+    ("uk", "Ukrainian"),
+    ("zh", "Chinese"),
+];
+
+// the English name of a language tag such as `ja`, `de-DE` or `de_DE`.
+// anything else, e.g. `Japanese`, is passed on as it is.
+pub fn language_name(tag: &str) -> String {
+    let primary = tag
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    LANGUAGE_NAMES
+        .iter()
+        .find(|(code, _)| *code == primary)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| tag.to_string())
+}
+
+pub fn render(template: &str, context: &PromptContext) -> Result<String, anyhow::Error> {
+    let mut rendered = String::with_capacity(template.len());
+    for part in parse(template).0 {
+        match part {
+            Part::Text(text) => rendered.push_str(text),
+            Part::Variable { name, line } => {
+                let value = context
+                    .value(name)
+                    .map_err(|e| anyhow::anyhow!("line {}: {}", line, e))?;
+                rendered.push_str(&value);
+            }
+        }
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_with_profile(template: &str) -> String {
+        let context = PromptContext {
+            image: Path::new("shot.png"),
+            profile: Some("work"),
+            locale: None,
+        };
+        render(template, &context).unwrap()
+    }
+
+    #[test]
+    fn known_variables_are_replaced() {
+        assert_eq!(render_with_profile("for {{ profile }}"), "for work");
+    }
+
+    #[test]
+    fn other_braces_are_sent_as_written() {
+        let json = r#"answer like {{"name": "blue-sky"}} or {% raw %}{{"#;
+        assert_eq!(render_with_profile(json), json);
+        assert_eq!(check(json).len(), 1);
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        assert_eq!(render_with_profile(r"\{{profile}}"), "{{profile}}");
+        assert!(check(r"\{{profile}}").is_empty());
+    }
+
+    #[test]
+    fn misspelled_variable_warns() {
+        let warnings = check("taken on {{daet}}");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("line 1: unknown variable `{{daet}}`"));
+        assert_eq!(render_with_profile("{{daet}}"), "{{daet}}");
+    }
+}
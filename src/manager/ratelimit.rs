@@ -0,0 +1,174 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use chrono::{DateTime, Local};
+use log::{error, info};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::setup::state_dir, utils::lock::FileLock};
+
+// a provider's quota, shared by every peeksy process using the same config
+// dir
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct RateLimit {
+    #[schemars(description = "Requests per minute")]
+    pub requests_per_minute: Option<u32>,
+    #[schemars(description = "Prompt and completion tokens per minute")]
+    pub tokens_per_minute: Option<u32>,
+}
+
+// the limit for the provider at base_url, matched with or without a
+// trailing slash
+pub fn limit_for(limits: &BTreeMap<String, RateLimit>, base_url: &str) -> Option<RateLimit> {
+    limits
+        .iter()
+        .find(|(url, _)| url.trim_end_matches('/') == base_url.trim_end_matches('/'))
+        .map(|(_, limit)| *limit)
+}
+
+// what is left of a provider's quota. both buckets refill continuously up to
+// a minute's worth and the token one may go below zero when a request used
+// more than was estimated.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Bucket {
+    requests: f64,
+    tokens: f64,
+    updated: DateTime<Local>,
+}
+
+fn get_state_path() -> Result<PathBuf, anyhow::Error> {
+    Ok(state_dir()?.join("ratelimit.json"))
+}
+
+fn read() -> BTreeMap<String, Bucket> {
+    get_state_path()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+fn write(buckets: &BTreeMap<String, Bucket>) -> Result<(), anyhow::Error> {
+    let path = get_state_path()?;
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, serde_json::to_string(buckets)?)?;
+    std::fs::rename(&temp, &path)?;
+    Ok(())
+}
+
+// a minute's worth of a limit, the most a bucket holds
+fn capacity(limit: Option<u32>) -> f64 {
+    limit.map_or(0.0, f64::from)
+}
+
+// takes from provider's buckets under the lock, refilling them first.
+// `take` returns how long to wait instead when there is not enough left.
+// runs on a blocking thread, as the lock may wait for another process.
+async fn update(
+    provider: &str,
+    limit: RateLimit,
+    take: impl FnOnce(&mut Bucket) -> Option<Duration> + Send + 'static,
+) -> Result<Option<Duration>, anyhow::Error> {
+    let provider = provider.to_string();
+    tokio::task::spawn_blocking(move || update_locked(&provider, limit, take))
+        .await
+        .unwrap_or_else(|e| Err(e.into()))
+}
+
+fn update_locked(
+    provider: &str,
+    limit: RateLimit,
+    take: impl FnOnce(&mut Bucket) -> Option<Duration>,
+) -> Result<Option<Duration>, anyhow::Error> {
+    let _lock = FileLock::acquire(&state_dir()?.join("ratelimit.lock"))?;
+    let mut buckets = read();
+    let now = Local::now();
+    let (rpm, tpm) = (
+        capacity(limit.requests_per_minute),
+        capacity(limit.tokens_per_minute),
+    );
+    let bucket = buckets.entry(provider.to_string()).or_insert(Bucket {
+        requests: rpm,
+        tokens: tpm,
+        updated: now,
+    });
+    let minutes = (now - bucket.updated).num_milliseconds().max(0) as f64 / 60_000.0;
+    bucket.requests = (bucket.requests + minutes * rpm).min(rpm);
+    bucket.tokens = (bucket.tokens + minutes * tpm).min(tpm);
+    bucket.updated = now;
+
+    let wait = take(bucket);
+    write(&buckets)?;
+    Ok(wait)
+}
+
+// waits until provider has room for one request of about tokens tokens,
+// then takes them. a request bigger than a whole minute only waits for a
+// full bucket.
+pub async fn acquire(provider: &str, limit: RateLimit, tokens: u64) {
+    let (rpm, tpm) = (
+        capacity(limit.requests_per_minute),
+        capacity(limit.tokens_per_minute),
+    );
+    let needed = (tokens as f64).min(tpm);
+    loop {
+        let wait = update(provider, limit, move |bucket| {
+            // minutes until each bucket has enough, zero without a limit
+            let for_requests = if rpm > 0.0 {
+                (1.0 - bucket.requests).max(0.0) / rpm
+            } else {
+                0.0
+            };
+            let for_tokens = if tpm > 0.0 {
+                (needed - bucket.tokens).max(0.0) / tpm
+            } else {
+                0.0
+            };
+            let minutes = for_requests.max(for_tokens);
+            if minutes > 0.0 {
+                return Some(Duration::from_secs_f64(minutes * 60.0));
+            }
+            if rpm > 0.0 {
+                bucket.requests -= 1.0;
+            }
+            if tpm > 0.0 {
+                bucket.tokens -= tokens as f64;
+            }
+            None
+        })
+        .await;
+        match wait {
+            Ok(None) => return,
+            Ok(Some(wait)) => {
+                info!(
+                    "[Rate Limit] Waiting {:.1}s for {}",
+                    wait.as_secs_f64(),
+                    provider
+                );
+                tokio::time::sleep(wait).await;
+            }
+            // a broken state file should not stop renaming
+            Err(e) => {
+                error!("Failed to update the rate limit state: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+// corrects the token bucket once the response says how many tokens the
+// request really used
+pub async fn settle(provider: &str, limit: RateLimit, estimated: u64, used: u64) {
+    if limit.tokens_per_minute.is_none() || estimated == used {
+        return;
+    }
+    let result = update(provider, limit, move |bucket| {
+        bucket.tokens -= used as f64 - estimated as f64;
+        None
+    })
+    .await;
+    if let Err(e) = result {
+        error!("Failed to update the rate limit state: {}", e);
+    }
+}
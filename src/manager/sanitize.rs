@@ -0,0 +1,53 @@
+use unicode_normalization::UnicodeNormalization;
+
+// ext4 and APFS both cap a file name at 255 bytes of UTF-8, which is only 85
+// characters of Japanese
+pub const MAX_NAME_BYTES: usize = 255;
+
+// characters that are not allowed, or cause trouble, in a file name on
+// macOS, Linux or a shared Windows drive
+const FORBIDDEN: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+// a value that goes into a file name, composed to NFC so the same name from
+// the model always gives the same bytes on disk
+pub fn component(value: &str) -> String {
+    value
+        .nfc()
+        .map(|c| {
+            if c.is_control() || FORBIDDEN.contains(&c) {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+// the longest start of text that fits in max_bytes, cut between characters
+pub fn truncate(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let end = (0..=max_bytes)
+        .rev()
+        .find(|i| text.is_char_boundary(*i))
+        .unwrap_or(0);
+    &text[..end]
+}
+
+// a stem that is safe to use as is. a leading dot would hide the file and
+// trailing dots or spaces are dropped by Windows and Finder.
+pub fn stem(stem: &str) -> String {
+    let stem = stem
+        .trim()
+        .trim_start_matches('.')
+        .trim_end_matches(['.', ' '])
+        .to_string();
+    if stem.is_empty() {
+        "unnamed".to_string()
+    } else {
+        stem
+    }
+}
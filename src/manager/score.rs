@@ -0,0 +1,84 @@
+use crate::manager::{naming::Suggestion, style};
+
+// words the default prompt asks the model to avoid
+pub const DEFAULT_BANNED_WORDS: &[&str] = &["image", "picture", "photo", "screenshot"];
+
+// the length the default prompt asks for
+const MIN_WORDS: usize = 3;
+const MAX_WORDS: usize = 8;
+
+// a possible name for an image, with how well it follows the naming rules
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub file_name: String,
+    pub suggestion: Suggestion,
+    pub score: i32,
+    // rules the name breaks, empty for a valid name
+    pub problems: Vec<String>,
+}
+
+impl Candidate {
+    // scores file_name. taken is whether another file already has it, in
+    // which case the name is numbered before it is used.
+    pub fn new(file_name: String, suggestion: Suggestion, taken: bool, banned: &[String]) -> Self {
+        let mut score = 100;
+        let mut problems = vec![];
+
+        let words: Vec<String> = style::words(&suggestion.name)
+            .iter()
+            .map(|word| word.to_lowercase())
+            .collect();
+        if words.is_empty() || words == ["unknown", "name"] {
+            score -= 100;
+            problems.push("the model gave no name".to_string());
+        }
+        for word in words.iter().filter(|word| banned.contains(word)) {
+            score -= 30;
+            problems.push(format!("uses the generic word `{}`", word));
+        }
+        // scripts without spaces between words, such as Japanese, give a
+        // single word however long the name is
+        if suggestion.name.is_ascii() && !words.is_empty() {
+            let count = words.len();
+            let off_by = MIN_WORDS.saturating_sub(count) + count.saturating_sub(MAX_WORDS);
+            if off_by > 0 {
+                score -= 10 * off_by as i32;
+                problems.push(format!(
+                    "has {} word{}, {} to {} expected",
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    MIN_WORDS,
+                    MAX_WORDS
+                ));
+            }
+        }
+        if taken {
+            score -= 50;
+            problems.push(format!("{} already exists", file_name));
+        }
+
+        Self {
+            file_name,
+            suggestion,
+            score,
+            problems,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// candidates best first: valid ones before the rest, then by score, the
+// model's order breaking ties. repeated names are dropped.
+pub fn rank(candidates: Vec<Candidate>) -> Vec<Candidate> {
+    let mut ranked: Vec<Candidate> = vec![];
+    for candidate in candidates {
+        if !ranked.iter().any(|c| c.file_name == candidate.file_name) {
+            ranked.push(candidate);
+        }
+    }
+    ranked.sort_by_key(|c| (!c.is_valid(), -c.score));
+    ranked
+}
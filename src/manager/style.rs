@@ -0,0 +1,92 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// how the words of a generated name are joined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamingStyle {
+    // login-form-error
+    Kebab,
+    // login_form_error
+    Snake,
+    // loginFormError
+    Camel,
+    // LoginFormError
+    Pascal,
+    // Login Form Error
+    Title,
+}
+
+// splits text into words at anything that is not a letter or digit and
+// where the case changes, so `loginForm`, `login_form` and `Login Form` all
+// give the same words
+pub fn words(text: &str) -> Vec<String> {
+    let mut words = vec![];
+    for chunk in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = chunk.chars().collect();
+        let mut word = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let previous = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1);
+            // `formError` splits before `E`, `HTTPServer` before `S`
+            let boundary = c.is_uppercase()
+                && previous.is_some_and(|p| {
+                    p.is_lowercase()
+                        || p.is_numeric()
+                        || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+                });
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.push(c);
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    words
+}
+
+fn capitalized(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+// text with its words joined in style, transliterated to ASCII first when
+// asked. text without any words is returned as it is.
+pub fn apply(text: &str, style: Option<NamingStyle>, transliterate: bool) -> String {
+    let text = if transliterate {
+        deunicode::deunicode(text)
+    } else {
+        text.to_string()
+    };
+    let Some(style) = style else {
+        return text;
+    };
+
+    let words = words(&text);
+    if words.is_empty() {
+        return text;
+    }
+    let lower = words.iter().map(|word| word.to_lowercase());
+    match style {
+        NamingStyle::Kebab => lower.collect::<Vec<_>>().join("-"),
+        NamingStyle::Snake => lower.collect::<Vec<_>>().join("_"),
+        NamingStyle::Camel => lower
+            .enumerate()
+            .map(|(i, word)| if i == 0 { word } else { capitalized(&word) })
+            .collect(),
+        NamingStyle::Pascal => words.iter().map(|word| capitalized(word)).collect(),
+        NamingStyle::Title => words
+            .iter()
+            .map(|word| capitalized(word))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{budget::Budget, setup::state_dir};
+
+// one request to the model, appended to usage.jsonl in the state dir. lines
+// are short enough for the append to be atomic, so the daemon and the cli
+// can write at the same time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub time: DateTime<Local>,
+    pub model: String,
+    pub profile: Option<String>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    // None for models without a known price
+    pub cost_usd: Option<f64>,
+}
+
+fn get_usage_path() -> Result<PathBuf, anyhow::Error> {
+    Ok(state_dir()?.join("usage.jsonl"))
+}
+
+// how much of the log is read at a time, from the end
+const CHUNK_BYTES: u64 = 64 * 1024;
+
+pub fn record(record: &Record) {
+    let result = serde_json::to_string(record)
+        .map_err(anyhow::Error::from)
+        .and_then(|line| {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(get_usage_path()?)?;
+            file.write_all(format!("{}\n", line).as_bytes())?;
+            Ok(())
+        });
+    if let Err(e) = result {
+        error!("Failed to record usage: {}", e);
+    }
+}
+
+// every record at or after since, oldest first. unreadable lines are skipped.
+pub fn records_since(since: DateTime<Local>) -> Vec<Record> {
+    let mut records = vec![];
+    if let Err(e) = read_back(since, &mut records) {
+        if e.downcast_ref::<std::io::Error>()
+            .is_none_or(|e| e.kind() != std::io::ErrorKind::NotFound)
+        {
+            error!("Failed to read usage: {}", e);
+        }
+    }
+    records.reverse();
+    records
+}
+
+// collects records newest first. the log is only ever appended to, so it is
+// read backwards and only as far as since, however old it gets.
+fn read_back(since: DateTime<Local>, records: &mut Vec<Record>) -> Result<(), anyhow::Error> {
+    let mut file = File::open(get_usage_path()?)?;
+    let mut end = file.metadata()?.len();
+    // the start of a line cut off by the previous chunk
+    let mut rest = vec![];
+    while end > 0 {
+        let start = end.saturating_sub(CHUNK_BYTES);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut chunk)?;
+        chunk.extend_from_slice(&rest);
+
+        let mut lines = chunk.split(|b| *b == b'\n');
+        rest = match start {
+            0 => vec![],
+            _ => lines.next().unwrap_or_default().to_vec(),
+        };
+        for line in lines.rev() {
+            let Ok(record) = serde_json::from_slice::<Record>(line) else {
+                continue;
+            };
+            if record.time < since {
+                return Ok(());
+            }
+            records.push(record);
+        }
+        end = start;
+    }
+    Ok(())
+}
+
+pub fn spent_since(since: DateTime<Local>) -> f64 {
+    records_since(since)
+        .iter()
+        .filter_map(|record| record.cost_usd)
+        .sum()
+}
+
+pub fn start_of_day() -> DateTime<Local> {
+    let today = Local::now().date_naive().and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&today)
+        .earliest()
+        .unwrap_or_else(Local::now)
+}
+
+pub fn start_of_month() -> DateTime<Local> {
+    let today = Local::now().date_naive();
+    let first = today.with_day(1).unwrap_or(today).and_time(NaiveTime::MIN);
+    Local
+        .from_local_datetime(&first)
+        .earliest()
+        .unwrap_or_else(Local::now)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BudgetState {
+    Within,
+    // past a soft cap, with a description of it
+    Soft(String),
+    // past a hard cap
+    Hard(String),
+}
+
+// where today's and this month's spending stands against budget
+pub fn budget_state(budget: &Budget) -> BudgetState {
+    if !budget.has_caps() {
+        return BudgetState::Within;
+    }
+
+    let month = records_since(start_of_month());
+    let since_day = start_of_day();
+    let monthly: f64 = month.iter().filter_map(|r| r.cost_usd).sum();
+    let daily: f64 = month
+        .iter()
+        .filter(|r| r.time >= since_day)
+        .filter_map(|r| r.cost_usd)
+        .sum();
+
+    let reached = |spent: f64, cap: Option<f64>, name: &str| {
+        cap.filter(|cap| spent >= *cap).map(|cap| {
+            format!(
+                "{} budget of ${:.2} reached (${:.2} spent)",
+                name, cap, spent
+            )
+        })
+    };
+    if let Some(reason) = reached(daily, budget.daily_hard_usd, "daily hard")
+        .or_else(|| reached(monthly, budget.monthly_hard_usd, "monthly hard"))
+    {
+        return BudgetState::Hard(reason);
+    }
+    if let Some(reason) = reached(daily, budget.daily_soft_usd, "daily soft")
+        .or_else(|| reached(monthly, budget.monthly_soft_usd, "monthly soft"))
+    {
+        return BudgetState::Soft(reason);
+    }
+    BudgetState::Within
+}
+
+// returned instead of a name while a hard budget is reached, so the daemon
+// can hold the screenshot until the budget resets
+#[derive(Debug)]
+pub struct BudgetExceeded(pub String);
+
+impl fmt::Display for BudgetExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for BudgetExceeded {}
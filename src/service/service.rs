@@ -0,0 +1,126 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{config::Config, setup};
+#[cfg(target_os = "macos")]
+use crate::launchd::launchd::LaunchD;
+#[cfg(not(target_os = "macos"))]
+use crate::systemd::systemd::Systemd;
+
+// when the service manager should bring a stopped daemon back
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    Never,
+    #[default]
+    OnFailure,
+    Always,
+}
+
+// everything needed to render a plist or unit file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceDefinition {
+    pub exe: PathBuf,
+    // when unset the service manager's default log destination is used
+    pub stdout_path: Option<PathBuf>,
+    pub stderr_path: Option<PathBuf>,
+    pub environment: BTreeMap<String, String>,
+    pub restart: RestartPolicy,
+}
+
+impl ServiceDefinition {
+    pub fn from_config(config: &Config) -> Result<Self, anyhow::Error> {
+        let exe = installed_exe()?;
+
+        // the daemon must read the same config file as the installing command
+        let mut environment = config.service_environment.clone().unwrap_or_default();
+        let config_path = setup::get_config_path();
+        if config_path != setup::get_default_config_path() {
+            let config_path = config_path.canonicalize().unwrap_or(config_path);
+            environment
+                .entry("PEEKSY_CONFIG".to_string())
+                .or_insert_with(|| config_path.to_string_lossy().to_string());
+        }
+
+        Ok(Self {
+            exe,
+            stdout_path: config.service_stdout_path.as_ref().map(PathBuf::from),
+            stderr_path: config.service_stderr_path.as_ref().map(PathBuf::from),
+            environment,
+            restart: config.service_restart.unwrap_or_default(),
+        })
+    }
+}
+
+// the binary the service should run. package managers such as Homebrew
+// keep the real binary in a versioned directory that an upgrade removes, so
+// the PATH entry pointing at it is preferred over the resolved path.
+fn installed_exe() -> Result<PathBuf, anyhow::Error> {
+    let exe = std::env::current_exe()
+        .map_err(|e| anyhow::anyhow!("Failed to resolve the peeksy binary path: {}", e))?;
+    let Some(name) = exe.file_name() else {
+        return Ok(exe);
+    };
+    let resolved = exe.canonicalize().unwrap_or_else(|_| exe.clone());
+    let on_path = std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(name))
+            .filter(|candidate| candidate.is_absolute())
+            .find(|candidate| candidate.canonicalize().is_ok_and(|c| c == resolved))
+    });
+    Ok(on_path.unwrap_or(exe))
+}
+
+// the platform's user-level service manager, which keeps `peeksy daemon`
+// running in the background
+pub trait ServiceManager {
+    fn name(&self) -> &'static str;
+
+    // path of the plist or unit file
+    fn definition_path(&self) -> PathBuf;
+
+    fn render(&self, definition: &ServiceDefinition) -> String;
+
+    fn is_installed(&self) -> bool {
+        self.definition_path().exists()
+    }
+
+    fn install(&self, definition: &ServiceDefinition) -> Result<(), anyhow::Error> {
+        let path = self.definition_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, self.render(definition))?;
+        Ok(())
+    }
+
+    fn uninstall(&self) -> Result<(), anyhow::Error> {
+        let path = self.definition_path();
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    // the service definition is known to the service manager
+    fn is_loaded(&self) -> bool;
+
+    // the daemon process is up
+    fn is_running(&self) -> bool;
+
+    fn load(&self);
+
+    fn unload(&self);
+}
+
+#[cfg(target_os = "macos")]
+pub fn service_manager() -> Box<dyn ServiceManager> {
+    Box::new(LaunchD::new())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn service_manager() -> Box<dyn ServiceManager> {
+    Box::new(Systemd::new())
+}
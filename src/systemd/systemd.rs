@@ -0,0 +1,256 @@
+use std::{
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+use log::{error, info};
+
+use crate::service::service::{RestartPolicy, ServiceDefinition, ServiceManager};
+
+const UNIT_NAME: &str = "peeksy.service";
+
+fn restart(restart: RestartPolicy) -> &'static str {
+    match restart {
+        RestartPolicy::Never => "no",
+        RestartPolicy::OnFailure => "on-failure",
+        RestartPolicy::Always => "always",
+    }
+}
+
+// journald is the natural log destination under systemd, files are only
+// used when a path is configured
+fn output(path: &Option<PathBuf>) -> String {
+    match path {
+        Some(path) => quote_arg(&escape(&format!("append:{}", path.display()))),
+        None => "journal".to_string(),
+    }
+}
+
+// renders the systemd user unit running `<exe> daemon`. kept free of any
+// systemd calls so the output can be checked without a live systemd.
+pub fn unit_file(definition: &ServiceDefinition) -> String {
+    let mut environment = String::new();
+    for (key, value) in &definition.environment {
+        environment.push_str(&format!(
+            "Environment={}\n",
+            quote_arg(&escape(&format!("{}={}", key, value)))
+        ));
+    }
+
+    format!(
+        r#"[Unit]
+Description=Peeksy screenshot renamer
+After=default.target
+
+[Service]
+Type=simple
+ExecStart={} daemon
+{}Restart={}
+KillSignal=SIGTERM
+TimeoutStopSec=30
+StandardOutput={}
+StandardError={}
+
+[Install]
+WantedBy=default.target
+"#,
+        quote_arg(&escape(&definition.exe.to_string_lossy())),
+        environment,
+        restart(definition.restart),
+        output(&definition.stdout_path),
+        output(&definition.stderr_path),
+    )
+}
+
+// `%` starts a specifier such as `%h` in most unit settings
+fn escape(value: &str) -> String {
+    value.replace('%', "%%")
+}
+
+// systemd splits ExecStart on whitespace unless the argument is quoted
+fn quote_arg(arg: &str) -> String {
+    if arg.chars().any(char::is_whitespace) || arg.contains('"') {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+pub struct Systemd {
+    unit_path: PathBuf,
+}
+
+impl Default for Systemd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Systemd {
+    pub fn new() -> Self {
+        let unit_path = dirs::config_dir()
+            .unwrap()
+            .join("systemd/user")
+            .join(UNIT_NAME);
+
+        Self { unit_path }
+    }
+
+    fn systemctl(&self, args: &[&str]) -> Option<Output> {
+        match Command::new("systemctl").arg("--user").args(args).output() {
+            Ok(output) => Some(output),
+            Err(e) => {
+                error!("Failed to run systemctl: {}", e);
+                None
+            }
+        }
+    }
+
+    fn systemctl_ok(&self, args: &[&str]) -> bool {
+        self.systemctl(args)
+            .is_some_and(|output| output.status.success())
+    }
+}
+
+impl ServiceManager for Systemd {
+    fn name(&self) -> &'static str {
+        "systemd"
+    }
+
+    fn definition_path(&self) -> PathBuf {
+        self.unit_path.clone()
+    }
+
+    fn render(&self, definition: &ServiceDefinition) -> String {
+        unit_file(definition)
+    }
+
+    fn install(&self, definition: &ServiceDefinition) -> Result<(), anyhow::Error> {
+        if let Some(parent) = self.unit_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.unit_path, unit_file(definition))?;
+        self.systemctl_ok(&["daemon-reload"]);
+        Ok(())
+    }
+
+    fn uninstall(&self) -> Result<(), anyhow::Error> {
+        // only uninstalling turns off the start at login
+        if self.is_loaded() {
+            if let Some(output) = self.systemctl(&["disable", "--now", UNIT_NAME]) {
+                if !output.status.success() {
+                    error!(
+                        "Failed to disable systemd unit: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    );
+                }
+            }
+        }
+        if self.unit_path.exists() {
+            std::fs::remove_file(&self.unit_path)?;
+        }
+        self.systemctl_ok(&["daemon-reload"]);
+        Ok(())
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.systemctl_ok(&["is-enabled", "--quiet", UNIT_NAME])
+    }
+
+    fn is_running(&self) -> bool {
+        self.systemctl_ok(&["is-active", "--quiet", UNIT_NAME])
+    }
+
+    fn load(&self) {
+        if self.is_running() {
+            info!("systemd unit is already running");
+            return;
+        }
+
+        info!("loading systemd unit path: {}", self.unit_path.display());
+        match self.systemctl(&["enable", "--now", UNIT_NAME]) {
+            Some(output) if output.status.success() => {
+                info!("systemd unit loaded successfully: {}", output.status)
+            }
+            Some(output) => error!(
+                "Failed to load systemd unit: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            None => {}
+        }
+    }
+
+    // stops the daemon but leaves the unit enabled, so it still starts at
+    // the next login as it does with launchd
+    fn unload(&self) {
+        if !self.is_running() {
+            info!("systemd unit is not running");
+            return;
+        }
+
+        info!("unloading systemd unit path: {}", self.unit_path.display());
+        match self.systemctl(&["stop", UNIT_NAME]) {
+            Some(output) if output.status.success() => {
+                info!("systemd unit unloaded successfully: {}", output.status)
+            }
+            Some(output) => error!(
+                "Failed to unload systemd unit: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn definition() -> ServiceDefinition {
+        ServiceDefinition {
+            exe: PathBuf::from("/Users/me/Application Support/peeksy"),
+            stdout_path: Some(PathBuf::from("/tmp/peeksy logs/out.log")),
+            stderr_path: None,
+            environment: BTreeMap::from([
+                ("HTTPS_PROXY".to_string(), "http://proxy:8080".to_string()),
+                (
+                    "PEEKSY_CONFIG".to_string(),
+                    "/tmp/100%/peeksy.json".to_string(),
+                ),
+            ]),
+            restart: RestartPolicy::Always,
+        }
+    }
+
+    #[test]
+    fn exec_start_quotes_the_binary() {
+        let unit = unit_file(&definition());
+        assert!(unit.contains("ExecStart=\"/Users/me/Application Support/peeksy\" daemon\n"));
+    }
+
+    #[test]
+    fn restart_policy() {
+        let mut definition = definition();
+        assert!(unit_file(&definition).contains("Restart=always\n"));
+        definition.restart = RestartPolicy::OnFailure;
+        assert!(unit_file(&definition).contains("Restart=on-failure\n"));
+        definition.restart = RestartPolicy::Never;
+        assert!(unit_file(&definition).contains("Restart=no\n"));
+    }
+
+    #[test]
+    fn environment_is_escaped() {
+        let unit = unit_file(&definition());
+        assert!(unit.contains("Environment=HTTPS_PROXY=http://proxy:8080\n"));
+        assert!(unit.contains("Environment=PEEKSY_CONFIG=/tmp/100%%/peeksy.json\n"));
+    }
+
+    #[test]
+    fn log_paths() {
+        let unit = unit_file(&definition());
+        assert!(unit.contains("StandardOutput=\"append:/tmp/peeksy logs/out.log\"\n"));
+        assert!(unit.contains("StandardError=journal\n"));
+    }
+}
@@ -0,0 +1,58 @@
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::io::AsRawFd,
+    path::Path,
+};
+
+// exclusive advisory lock on a file, held until dropped, for state in the
+// config dir that the daemon and the cli both rewrite. the kernel releases
+// it when the process dies, so a crash never leaves a lock behind.
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    // waits until no other process holds the lock. blocks the thread, so
+    // async code calls it from `spawn_blocking`.
+    pub fn acquire(path: &Path) -> Result<Self, anyhow::Error> {
+        let file = open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(Self { file })
+    }
+
+    // None when another process holds the lock
+    pub fn try_acquire(path: &Path) -> Result<Option<Self>, anyhow::Error> {
+        let file = open(path)?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+            let e = std::io::Error::last_os_error();
+            if e.kind() == std::io::ErrorKind::WouldBlock {
+                return Ok(None);
+            }
+            return Err(e.into());
+        }
+        Ok(Some(Self { file }))
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+}
+
+fn open(path: &Path) -> Result<File, std::io::Error> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe {
+            libc::flock(self.file.as_raw_fd(), libc::LOCK_UN);
+        }
+    }
+}
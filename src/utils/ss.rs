@@ -4,19 +4,19 @@ use anyhow::Error;
 use log::error;
 
 fn get_clean_path(raw: String, home: PathBuf) -> Result<PathBuf, Error> {
-    if raw.starts_with("~/") {
-        return Ok(home.join(raw[2..].to_string()));
+    if let Some(stripped) = raw.strip_prefix("~/") {
+        return Ok(home.join(stripped));
     }
 
     let raw_path = PathBuf::from(raw.clone());
     // if raw_path has home path, then return raw_path
     if raw_path.starts_with(&home) {
-        return Ok(raw_path);
+        Ok(raw_path)
     } else {
-        return Err(anyhow::anyhow!(
+        Err(anyhow::anyhow!(
             "Raw path {} is not understanding",
             raw_path.display()
-        ));
+        ))
     }
 }
 